@@ -1,8 +1,29 @@
 use tb_app::Application;
 use tb_core::error::*;
+use tb_engine::app_info::{AppInfo, AppInfoErrorKind};
+use tb_engine::settings::{Settings, SettingsErrorKind};
 
 error_chain! {}
 
 fn main() -> Result<()> {
-    Application::run().chain_err(|| "App running error")
+    match AppInfo::new(std::env::args().skip(1), |key| std::env::var(key).ok()) {
+        Ok(app_info) => {
+            let _ = AppInfo::init(app_info);
+        }
+        Err(e) => {
+            if let AppInfoErrorKind::Settings(SettingsErrorKind::HelpRequested) = e.kind() {
+                println!("{}", Settings::USAGE);
+                std::process::exit(0);
+            }
+            eprintln!("{}", e.display_chain());
+            eprintln!("{}", Settings::USAGE);
+            std::process::exit(2);
+        }
+    }
+
+    let code = Application::run().chain_err(|| "App running error")?;
+    if code != 0 {
+        std::process::exit(code);
+    }
+    Ok(())
 }
@@ -0,0 +1,345 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use tb_ecs::*;
+use tb_engine::app_exit::AppExit;
+use tb_engine::asset::AssetLoader;
+use tb_engine::level::{Level, LevelManager};
+use tb_engine::path::TbPath;
+use tb_engine::settings::{Settings, SettingsLayer};
+use tb_plugin::PluginManager;
+
+use crate::errors::*;
+
+/// How `App::run` runs the frames
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Runner {
+    /// Run frames paced at the fps of the settings, without sleeping if headless,
+    /// until `AppExit` is requested or the frame or time limit is reached
+    Fixed,
+    /// Run a single frame
+    Once,
+    /// Run no frame, the caller steps by `App::update`
+    Manual,
+}
+
+/// Assembles an `App`. Nothing is read from the command line or `AppInfo`, so it can be used in tests and tools
+pub struct AppBuilder {
+    settings: Settings,
+    resources: Vec<Box<dyn FnOnce(&mut World)>>,
+    systems: Vec<SystemInfo>,
+    plugin_dirs: Vec<PathBuf>,
+    plugins: Vec<String>,
+    level: Option<TbPath>,
+    runner: Runner,
+}
+
+impl Default for AppBuilder {
+    fn default() -> Self {
+        Self {
+            settings: Settings::new(SettingsLayer::default(), "toybox"),
+            resources: vec![],
+            systems: vec![],
+            plugin_dirs: vec![],
+            plugins: vec![],
+            level: None,
+            runner: Runner::Fixed,
+        }
+    }
+}
+
+impl AppBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Insert `resource` into the world, replacing the one inserted before
+    pub fn with_resource<R: Resource>(mut self, resource: R) -> Self {
+        self.resources.push(Box::new(move |world: &mut World| {
+            let mut resource = Some(resource);
+            let inserted = world.insert(|| resource.take().unwrap());
+            if let Some(resource) = resource {
+                *inserted = resource;
+            }
+        }));
+        self
+    }
+
+    /// Run `S` in this app only, for the systems not registered by `#[system]`
+    pub fn with_system<S>(mut self) -> Self
+    where
+        for<'r> S: 'static + Default + System<'r> + Sync,
+    {
+        self.systems.push(SystemInfo::new::<S>());
+        self
+    }
+
+    pub fn with_plugin_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.plugin_dirs.push(dir.into());
+        self
+    }
+
+    /// Load the plugin library named `lib_name` from the plugin dirs
+    pub fn with_plugin(mut self, lib_name: impl Into<String>) -> Self {
+        self.plugins.push(lib_name.into());
+        self
+    }
+
+    /// Load `level` and switch to it at start
+    pub fn with_level(mut self, level: TbPath) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    pub fn with_runner(mut self, runner: Runner) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// Fails if the settings can't be run with, such as a zero fps
+    pub fn build(self) -> Result<App> {
+        self.settings.validate().chain_err(|| "Invalid settings")?;
+        tb_engine::log::set_max_level(self.settings.log_level);
+
+        let mut world = World::default();
+        for insert_resource in self.resources {
+            insert_resource(&mut world);
+        }
+        world.insert(AppExit::default);
+
+        if !self.plugins.is_empty() {
+            let plugin_manager = world.insert(PluginManager::default);
+            for dir in self.plugin_dirs {
+                plugin_manager.add_search_dir(dir);
+            }
+            for plugin in &self.plugins {
+                plugin_manager.add_plugin(plugin);
+            }
+        }
+
+        if let Some(level) = self.level {
+            world.insert(LevelManager::default);
+            world.insert(AssetLoader::default);
+            let (mut level_manager, mut asset_loader) =
+                unsafe { <(Write<LevelManager>, Write<AssetLoader>)>::fetch(&world) };
+            let level = asset_loader.load::<Level>(level);
+            level_manager.request_switch(level);
+        }
+
+        let scheduler = Scheduler::with_systems(&mut world, self.systems);
+        Ok(App {
+            world,
+            scheduler,
+            settings: self.settings,
+            runner: self.runner,
+            frames: 0,
+            start: Instant::now(),
+        })
+    }
+
+    /// Build and run, returns the exit status
+    pub fn run(self) -> Result<i32> {
+        Ok(self.build()?.run())
+    }
+}
+
+pub struct App {
+    world: World,
+    scheduler: Scheduler,
+    settings: Settings,
+    runner: Runner,
+    frames: u64,
+    start: Instant,
+}
+
+impl App {
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Number of the frames run
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Run one frame, returns the exit status if the app should quit
+    pub fn update(&mut self) -> Option<i32> {
+        self.scheduler.update(&mut self.world);
+        self.frames += 1;
+        self.exit_code()
+    }
+
+    /// Run by the runner, returns the exit status requested by `AppExit`,
+    /// 0 if stopped by the frame or time limit or by the runner
+    pub fn run(&mut self) -> i32 {
+        match self.runner {
+            Runner::Fixed => self.run_fixed(),
+            Runner::Once => self.update().unwrap_or(0),
+            Runner::Manual => self.exit_code().unwrap_or(0),
+        }
+    }
+
+    fn run_fixed(&mut self) -> i32 {
+        let frame_duration = Duration::from_secs_f32(1f32 / self.settings.fps);
+        loop {
+            if let Some(code) = self.exit_code() {
+                return code;
+            }
+
+            let start = Instant::now();
+
+            self.scheduler.update(&mut self.world);
+            self.frames += 1;
+
+            if self.settings.headless {
+                continue;
+            }
+
+            let elapsed = start.elapsed();
+            if frame_duration > elapsed {
+                let should_sleep = frame_duration - elapsed;
+                std::thread::sleep(should_sleep);
+            } else {
+                std::thread::yield_now();
+            }
+        }
+    }
+
+    fn exit_code(&mut self) -> Option<i32> {
+        if let Some(code) = self.world.insert(AppExit::default).code() {
+            return Some(code);
+        }
+        let frames_reached = self
+            .settings
+            .frames
+            .map_or(false, |limit| self.frames >= limit);
+        let time_reached = self.settings.time_limit.map_or(false, |limit| {
+            self.start.elapsed() >= Duration::from_secs_f32(limit)
+        });
+        if frames_reached || time_reached {
+            Some(0)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tb_ecs::*;
+    use tb_engine::app_exit::AppExit;
+    use tb_engine::settings::{Settings, SettingsLayer};
+
+    use crate::{AppBuilder, Runner};
+
+    #[derive(Default)]
+    struct FrameCounter {
+        frames: u64,
+        exit_at: u64,
+    }
+
+    #[derive(Default)]
+    struct CountFrames {}
+
+    impl<'r> System<'r> for CountFrames {
+        type SystemData = (Write<'r, FrameCounter>, Write<'r, AppExit>);
+
+        fn run(&mut self, (mut counter, mut exit): Self::SystemData) {
+            counter.frames += 1;
+            if counter.frames == counter.exit_at {
+                exit.request(7);
+            }
+        }
+    }
+
+    fn frames(app: &crate::App) -> u64 {
+        unsafe { app.world().fetch::<FrameCounter>() }.frames
+    }
+
+    #[test]
+    fn build_and_run() {
+        let settings = Settings::new(
+            SettingsLayer {
+                frames: Some(5),
+                headless: Some(true),
+                ..Default::default()
+            },
+            "test",
+        );
+        let mut app = AppBuilder::new()
+            .with_settings(settings.clone())
+            .with_resource(FrameCounter::default())
+            .with_system::<CountFrames>()
+            .build()
+            .unwrap();
+        assert_eq!(app.run(), 0);
+        assert_eq!(app.frames(), 5);
+        assert_eq!(frames(&app), 5);
+
+        let mut app = AppBuilder::new()
+            .with_settings(settings.clone())
+            .with_resource(FrameCounter {
+                frames: 0,
+                exit_at: 3,
+            })
+            .with_system::<CountFrames>()
+            .build()
+            .unwrap();
+        assert_eq!(app.run(), 7);
+        assert_eq!(frames(&app), 3);
+
+        let mut app = AppBuilder::new()
+            .with_settings(settings.clone())
+            .with_resource(FrameCounter::default())
+            .with_system::<CountFrames>()
+            .with_runner(Runner::Manual)
+            .build()
+            .unwrap();
+        assert_eq!(app.run(), 0);
+        assert_eq!(app.frames(), 0);
+        assert_eq!(app.update(), None);
+        assert_eq!(frames(&app), 1);
+
+        // The systems of the apps above are not run by the others
+        let mut app = AppBuilder::new()
+            .with_settings(settings)
+            .with_resource(FrameCounter::default())
+            .with_runner(Runner::Manual)
+            .build()
+            .unwrap();
+        assert_eq!(app.update(), None);
+        assert_eq!(frames(&app), 0);
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let build = |layer: SettingsLayer| {
+            AppBuilder::new()
+                .with_settings(Settings::new(layer, "test"))
+                .build()
+        };
+        assert!(build(SettingsLayer {
+            fps: Some(0f32),
+            ..Default::default()
+        })
+        .is_err());
+        assert!(build(SettingsLayer {
+            time_limit: Some(-1f32),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
@@ -1,13 +1,10 @@
 use std::process::Command;
-use std::time::{Duration, Instant};
 
+pub use builder::*;
 use errors::*;
-use tb_ecs::*;
 use tb_engine::app_info::{AppInfo, LaunchMethod};
-use tb_engine::asset::AssetLoader;
-use tb_engine::level::{Level, LevelManager};
-use tb_engine::path::TbPath;
-use tb_plugin::PluginManager;
+
+mod builder;
 
 mod errors {
     pub use tb_core::error::*;
@@ -15,22 +12,22 @@ mod errors {
     error_chain! {}
 }
 
+/// Runs the project or the archive described by `AppInfo`
 #[derive(Default)]
 pub struct Application {}
 
 impl Application {
-    pub fn run() -> Result<()> {
-        let mut app = Self::default();
-        let mut world = World::default();
-        app.setup_project(&mut world)?;
-        app.setup_entry_level(&mut world)?;
-        app.main_loop(&mut world);
-        Ok(())
+    /// Returns the exit status requested by `AppExit`, 0 if stopped by the frame or time limit
+    pub fn run() -> Result<i32> {
+        Self::builder()?.run()
     }
 
-    fn setup_project(&mut self, world: &mut World) -> Result<()> {
-        let plugin_manager: &mut PluginManager = world.insert(PluginManager::default);
+    /// The builder of the app set up by `AppInfo`, building the project first if launched as `Project`
+    pub fn builder() -> Result<AppBuilder> {
         let app_info = AppInfo::get();
+        let mut builder = AppBuilder::new()
+            .with_settings(app_info.settings.clone())
+            .with_level(app_info.settings.entry_level.clone());
         match &app_info.method {
             LaunchMethod::Project { project_dir } => {
                 if !project_dir.exists() {
@@ -46,13 +43,17 @@ impl Application {
                 } else {
                     project_dir.join("target/release")
                 };
-                plugin_manager.add_search_dir(project_lib_dir);
-                plugin_manager.add_plugin(project_dir.file_name().unwrap().to_str().unwrap())
+                builder = builder
+                    .with_plugin_dir(project_lib_dir)
+                    .with_plugin(project_dir.file_name().unwrap().to_str().unwrap())
             }
             LaunchMethod::Archive => {}
         }
+        for plugin in &app_info.settings.plugins {
+            builder = builder.with_plugin(plugin.as_str());
+        }
 
-        if !app_info.project_assets_dir.exists() {
+        if app_info.project_archive.is_none() && !app_info.project_assets_dir.exists() {
             std::fs::create_dir_all(&app_info.project_assets_dir).chain_err(|| {
                 format!(
                     "Failed to create asset dir: {:?}",
@@ -60,36 +61,6 @@ impl Application {
                 )
             })?;
         }
-        Ok(())
-    }
-
-    fn setup_entry_level(&self, world: &mut World) -> Result<()> {
-        let path = TbPath::new_project_assets("levels/entry.tbasset");
-        world.insert(LevelManager::default);
-        world.insert(AssetLoader::default);
-        let (mut level_manager, mut asset_loader) =
-            unsafe { <(Write<LevelManager>, Write<AssetLoader>)>::fetch(world) };
-        let level = asset_loader.load::<Level>(path);
-        level_manager.request_switch(level);
-        Ok(())
-    }
-
-    fn main_loop(&mut self, world: &mut World) {
-        let mut scheduler = Scheduler::new(world);
-        const FPS: f32 = 30f32;
-        let frame_duration = Duration::from_secs_f32(1f32 / FPS);
-        loop {
-            let start = Instant::now();
-
-            scheduler.update(world);
-
-            let elapsed = start.elapsed();
-            if frame_duration > elapsed {
-                let should_sleep = frame_duration - elapsed;
-                std::thread::sleep(should_sleep);
-            } else {
-                std::thread::yield_now();
-            }
-        }
+        Ok(builder)
     }
 }
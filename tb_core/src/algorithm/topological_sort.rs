@@ -1,31 +1,46 @@
-use std::collections::{HashMap, HashSet, LinkedList};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
 use std::hash::Hash;
 
 use rayon::prelude::*;
 
+pub use errors::{Error, ErrorKind, Result};
+
 mod errors {
     use crate::error::*;
 
     error_chain! {
         errors {
-            CircularDependency
+            CircularDependency(cycle: Vec<String>) {
+                description("Circular dependency"),
+                display("Circular dependency. cycle: {}", cycle.join(" -> ")),
+            }
         }
     }
 }
 
 pub struct Node<T> {
     item: T,
-    dependencies: HashSet<T>,
+    dependencies: Vec<T>,
 }
 
 impl<T> Node<T> {
-    pub fn dependencies(&self) -> &HashSet<T> {
+    pub fn item(&self) -> &T {
+        &self.item
+    }
+
+    /// Direct dependencies in insertion order
+    pub fn dependencies(&self) -> &[T] {
         &self.dependencies
     }
 }
 
+/// Items iterate in insertion order, dependencies before dependants
 pub struct TopologicalGraph<T: Eq + Hash + Clone> {
+    items: Vec<T>,
     nodes: HashMap<T, Node<T>>,
+    /// Memoized transitive dependencies, invalidated by the changes of the edges
+    reachable: HashMap<T, HashSet<T>>,
 }
 
 impl<T: Eq + Hash + Clone> TopologicalGraph<T> {
@@ -33,15 +48,43 @@ impl<T: Eq + Hash + Clone> TopologicalGraph<T> {
         self.nodes.get(item)
     }
 
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
     pub fn clear(&mut self) {
-        self.nodes.clear()
+        self.items.clear();
+        self.nodes.clear();
+        self.reachable.clear();
     }
 
     pub fn add_item(&mut self, item: T) {
-        self.nodes.entry(item).or_insert_with_key(|item| Node {
-            item: item.clone(),
-            dependencies: Default::default(),
-        });
+        if !self.nodes.contains_key(&item) {
+            self.items.push(item.clone());
+            self.nodes.insert(
+                item.clone(),
+                Node {
+                    item,
+                    dependencies: Default::default(),
+                },
+            );
+        }
+    }
+
+    /// Remove `item` and all the edges from and to it
+    pub fn remove_item(&mut self, item: &T) {
+        if self.nodes.remove(item).is_none() {
+            return;
+        }
+        self.items.retain(|i| i != item);
+        for node in self.nodes.values_mut() {
+            node.dependencies.retain(|dependency| dependency != item);
+        }
+        self.reachable.clear();
     }
 
     /// # Description
@@ -50,18 +93,27 @@ impl<T: Eq + Hash + Clone> TopologicalGraph<T> {
         if a == b {
             return;
         }
-        self.nodes.entry(b.clone()).or_insert_with_key(|b| Node {
-            item: b.clone(),
-            dependencies: Default::default(),
-        });
-        self.nodes
-            .entry(a)
-            .or_insert_with_key(|a| Node {
-                item: a.clone(),
-                dependencies: Default::default(),
-            })
-            .dependencies
-            .insert(b);
+        self.add_item(b.clone());
+        self.add_item(a.clone());
+        let node = self.nodes.get_mut(&a).unwrap();
+        if node.dependencies.contains(&b) {
+            return;
+        }
+        node.dependencies.push(b);
+        self.reachable
+            .retain(|item, reachable| *item != a && !reachable.contains(&a));
+    }
+
+    /// # Description
+    /// `a` no longer depend on `b` directly
+    pub fn remove_dependency(&mut self, a: &T, b: &T) {
+        if let Some(node) = self.nodes.get_mut(a) {
+            let len = node.dependencies.len();
+            node.dependencies.retain(|dependency| dependency != b);
+            if node.dependencies.len() != len {
+                self.reachable.clear();
+            }
+        }
     }
 
     /// # Description
@@ -76,42 +128,97 @@ impl<T: Eq + Hash + Clone> TopologicalGraph<T> {
         self.add_dependency(a, b)
     }
 
-    pub fn iter(&self) -> Iter<T> {
-        Iter::new(self)
+    /// Whether `a` depends on `b` directly or indirectly
+    pub fn is_dependent(&mut self, a: &T, b: &T) -> bool {
+        if !self.nodes.contains_key(a) {
+            return false;
+        }
+        if !self.reachable.contains_key(a) {
+            let reachable = self.collect_reachable(a);
+            self.reachable.insert(a.clone(), reachable);
+        }
+        self.reachable[a].contains(b)
     }
 
-    fn is_dependent(&self, a: &T, b: &T) -> bool {
-        let a = match self.nodes.get(a) {
-            None => {
-                return false;
+    fn collect_reachable(&self, from: &T) -> HashSet<T> {
+        let mut reachable = HashSet::new();
+        let mut stack = vec![from];
+        while let Some(item) = stack.pop() {
+            if let Some(node) = self.nodes.get(item) {
+                for dependency in &node.dependencies {
+                    if reachable.insert(dependency.clone()) {
+                        stack.push(dependency);
+                    }
+                }
             }
-            Some(a) => a,
-        };
-
-        if a.dependencies.contains(b) {
-            return true;
         }
+        reachable
+    }
 
-        for a in &a.dependencies {
-            if self.is_dependent(a, b) {
-                return true;
+    /// Remove the direct dependencies which are also reachable through other dependencies.
+    /// The result is unique only if the graph is acyclic
+    pub fn transitive_reduction(&mut self) {
+        let mut redundant = vec![];
+        for item in &self.items {
+            let dependencies = &self.nodes[item].dependencies;
+            for dependency in dependencies {
+                let indirect = dependencies
+                    .iter()
+                    .filter(|other| *other != dependency)
+                    .any(|other| self.collect_reachable(other).contains(dependency));
+                if indirect {
+                    redundant.push((item.clone(), dependency.clone()));
+                }
             }
         }
+        for (a, b) in redundant {
+            self.remove_dependency(&a, &b);
+        }
+    }
 
-        false
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter::new(self)
+    }
+}
+
+impl<T: Eq + Hash + Clone + Debug> TopologicalGraph<T> {
+    /// Group the items into layers, the items of a layer only depend on the items of the previous layers,
+    /// so they can be executed in parallel
+    pub fn levels(&self) -> Result<Vec<Vec<T>>> {
+        let mut item_to_level: HashMap<T, usize> = HashMap::with_capacity(self.items.len());
+        let mut levels: Vec<Vec<T>> = vec![];
+        for item in self.iter() {
+            let item = item?;
+            let level = self.nodes[&item]
+                .dependencies
+                .iter()
+                .map(|dependency| item_to_level[dependency] + 1)
+                .max()
+                .unwrap_or(0);
+            if levels.len() <= level {
+                levels.resize_with(level + 1, Vec::new);
+            }
+            levels[level].push(item.clone());
+            item_to_level.insert(item, level);
+        }
+        Ok(levels)
     }
 }
 
 impl<T: Eq + Hash + Clone + Sync> TopologicalGraph<T> {
-    pub fn par_iter(&self) -> rayon::collections::hash_map::Iter<'_, T, Node<T>> {
-        self.nodes.par_iter()
+    /// Items with their nodes in insertion order
+    pub fn par_iter(&self) -> impl IndexedParallelIterator<Item = (&T, &Node<T>)> {
+        let nodes = &self.nodes;
+        self.items.par_iter().map(move |item| (item, &nodes[item]))
     }
 }
 
 impl<T: Eq + Hash + Clone> Default for TopologicalGraph<T> {
     fn default() -> Self {
         Self {
+            items: Default::default(),
             nodes: Default::default(),
+            reachable: Default::default(),
         }
     }
 }
@@ -119,8 +226,8 @@ impl<T: Eq + Hash + Clone> Default for TopologicalGraph<T> {
 pub struct Iter<'d, T: Eq + Hash + Clone> {
     graph: &'d TopologicalGraph<T>,
     visited: HashSet<T>,
-    node_iter: std::collections::hash_map::Iter<'d, T, Node<T>>,
-    visiting_stack: LinkedList<(T, std::collections::hash_set::Iter<'d, T>)>,
+    item_iter: std::slice::Iter<'d, T>,
+    visiting_stack: Vec<(&'d T, std::slice::Iter<'d, T>)>,
     visiting_items: HashSet<T>,
     has_error: bool,
 }
@@ -130,7 +237,7 @@ impl<'d, T: Eq + Hash + Clone> Iter<'d, T> {
         Self {
             graph,
             visited: Default::default(),
-            node_iter: graph.nodes.iter(),
+            item_iter: graph.items.iter(),
             visiting_stack: Default::default(),
             visiting_items: Default::default(),
             has_error: false,
@@ -138,8 +245,8 @@ impl<'d, T: Eq + Hash + Clone> Iter<'d, T> {
     }
 }
 
-impl<'d, T: Eq + Hash + Clone> Iterator for Iter<'d, T> {
-    type Item = errors::Result<T>;
+impl<'d, T: Eq + Hash + Clone + Debug> Iterator for Iter<'d, T> {
+    type Item = Result<T>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.has_error {
@@ -148,36 +255,48 @@ impl<'d, T: Eq + Hash + Clone> Iterator for Iter<'d, T> {
 
         let visited = &self.visited;
         if self.visiting_stack.is_empty() {
-            match self.node_iter.find(|node| !visited.contains(node.0)) {
+            match self.item_iter.find(|item| !visited.contains(*item)) {
                 None => {
                     return None;
                 }
-                Some((item, node)) => {
+                Some(item) => {
                     self.visiting_items.insert(item.clone());
+                    let node = &self.graph.nodes[item];
                     self.visiting_stack
-                        .push_back((node.item.clone(), node.dependencies.iter()));
+                        .push((&node.item, node.dependencies.iter()));
                 }
             }
         };
 
         loop {
-            let current = self.visiting_stack.back_mut().unwrap();
+            let current = self.visiting_stack.last_mut().unwrap();
             if let Some(child) = current.1.next() {
                 if visited.contains(child) {
                 } else if self.visiting_items.insert(child.clone()) {
-                    let child_node = self.graph.nodes.get(child).unwrap();
+                    let child_node = &self.graph.nodes[child];
                     self.visiting_stack
-                        .push_back((child_node.item.clone(), child_node.dependencies.iter()));
+                        .push((&child_node.item, child_node.dependencies.iter()));
                 } else {
                     self.has_error = true;
-                    return Some(Err(errors::ErrorKind::CircularDependency.into()));
+                    let start = self
+                        .visiting_stack
+                        .iter()
+                        .position(|(item, _)| *item == child)
+                        .unwrap();
+                    let cycle = self.visiting_stack[start..]
+                        .iter()
+                        .map(|(item, _)| *item)
+                        .chain(std::iter::once(child))
+                        .map(|item| format!("{:?}", item))
+                        .collect();
+                    return Some(Err(ErrorKind::CircularDependency(cycle).into()));
                 }
             } else {
                 break;
             }
         }
 
-        let current = self.visiting_stack.pop_back().unwrap().0;
+        let current = self.visiting_stack.pop().unwrap().0.clone();
         self.visited.insert(current.clone());
         self.visiting_items.remove(&current);
         Some(Ok(current))
@@ -186,7 +305,7 @@ impl<'d, T: Eq + Hash + Clone> Iterator for Iter<'d, T> {
 
 #[cfg(test)]
 mod tests {
-    use crate::algorithm::topological_sort::TopologicalGraph;
+    use crate::algorithm::topological_sort::{ErrorKind, TopologicalGraph};
 
     #[test]
     fn it_works() {
@@ -216,4 +335,67 @@ mod tests {
             let _item = e.unwrap();
         }
     }
+
+    #[test]
+    fn cycle_path() {
+        let mut t = TopologicalGraph::default();
+        t.add_item(0);
+        t.add_dependency(1, 2);
+        t.add_dependency(2, 3);
+        t.add_dependency(3, 1);
+        let error = t.iter().find_map(|e| e.err()).unwrap();
+        match error.kind() {
+            ErrorKind::CircularDependency(cycle) => {
+                assert_eq!(cycle, &vec!["2", "3", "1", "2"]);
+            }
+            _ => unreachable!(),
+        }
+        assert!(t.levels().is_err());
+
+        t.remove_dependency(&3, &1);
+        assert_eq!(t.levels().unwrap(), vec![vec![0, 3], vec![2], vec![1]]);
+    }
+
+    #[test]
+    fn deterministic_levels() {
+        for _ in 0..10 {
+            let mut t = TopologicalGraph::default();
+            for item in ["e", "d", "c", "b", "a"] {
+                t.add_item(item);
+            }
+            t.add_dependency("a", "b");
+            t.add_dependency("a", "c");
+            t.add_dependency("b", "d");
+            t.add_dependency("c", "d");
+            t.add_dependency("a", "d");
+            let order: Vec<_> = t.iter().map(|e| e.unwrap()).collect();
+            assert_eq!(order, vec!["e", "d", "c", "b", "a"]);
+            assert_eq!(
+                t.levels().unwrap(),
+                vec![vec!["e", "d"], vec!["c", "b"], vec!["a"]]
+            );
+        }
+    }
+
+    #[test]
+    fn remove_and_reduce() {
+        let mut t = TopologicalGraph::default();
+        t.add_dependency(1, 2);
+        t.add_dependency(2, 3);
+        t.add_dependency(1, 3);
+        assert!(t.is_dependent(&1, &3));
+        assert!(!t.is_dependent(&3, &1));
+
+        t.transitive_reduction();
+        assert_eq!(t.node(&1).unwrap().dependencies(), &[2]);
+        assert!(t.is_dependent(&1, &3));
+
+        t.add_dependency_if_non_inverse(3, 1);
+        assert_eq!(t.node(&3).unwrap().dependencies(), &[] as &[i32]);
+
+        t.remove_item(&2);
+        assert_eq!(t.len(), 2);
+        assert!(!t.is_dependent(&1, &3));
+        assert_eq!(t.iter().map(|e| e.unwrap()).collect::<Vec<_>>(), vec![1, 3]);
+    }
 }
@@ -1,16 +1,34 @@
 use std::hash::Hash;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
 use std::mem::MaybeUninit;
-use std::ops::Index;
-use std::ptr;
+use std::ops::{Bound, Index, IndexMut, RangeBounds, Sub};
+use std::{ptr, slice};
 
 pub struct RingVec<T> {
     buf: Vec<MaybeUninit<T>>,
     start: usize,
     len: usize,
-    pop_counter: u64,
+    /// Cursor of the front element.
+    /// It starts in the middle of the range so that `push_front` can move it back
+    front_cursor: u64,
 }
 
+const INITIAL_FRONT_CURSOR: u64 = 1 << 63;
+
 impl<T> RingVec<T> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
     pub fn clear(&mut self) {
         self.remove_to_index(self.len);
     }
@@ -26,29 +44,69 @@ impl<T> RingVec<T> {
         }
     }
 
+    pub fn start_cursor(&self) -> RingCursor {
+        RingCursor {
+            cursor: self.front_cursor,
+        }
+    }
+
     pub fn end_cursor(&self) -> RingCursor {
         RingCursor {
-            cursor: self.pop_counter + self.len as u64,
+            cursor: self.front_cursor + self.len as u64,
         }
     }
 
     pub fn get_by_cursor(&self, cursor: RingCursor) -> Option<&T> {
         self.cursor_to_index(cursor)
-            .and_then(|index| self.get_by_index(index))
+            .and_then(|index| self.get(index))
+    }
+
+    pub fn get_mut_by_cursor(&mut self, cursor: RingCursor) -> Option<&mut T> {
+        self.cursor_to_index(cursor)
+            .and_then(move |index| self.get_mut(index))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            None
+        } else {
+            let index = self.physical_index(index);
+            Some(unsafe { (self.buf[index]).assume_init_ref() })
+        }
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.len {
+            None
+        } else {
+            let index = self.physical_index(index);
+            Some(unsafe { (self.buf[index]).assume_init_mut() })
+        }
     }
 
     pub fn push_back(&mut self, value: T) {
         self.reserve(1);
         unsafe {
-            let end = self
-                .buf
-                .as_mut_ptr()
-                .add((self.start + self.len) % self.buf.len());
+            let end = self.buf.as_mut_ptr().add(self.physical_index(self.len));
             ptr::write(end, MaybeUninit::new(value));
             self.len += 1;
         }
     }
 
+    /// Push `value` before the front, its cursor is the one before the old `start_cursor`
+    pub fn push_front(&mut self, value: T) {
+        self.reserve(1);
+        unsafe {
+            self.start = (self.start + self.buf.len() - 1) % self.buf.len();
+            ptr::write(
+                self.buf.as_mut_ptr().add(self.start),
+                MaybeUninit::new(value),
+            );
+            self.len += 1;
+            self.front_cursor -= 1;
+        }
+    }
+
     pub fn pop_front(&mut self) -> Option<T> {
         if self.len == 0 {
             None
@@ -57,12 +115,24 @@ impl<T> RingVec<T> {
                 let start = self.buf.as_ptr().add(self.start);
                 self.start = (self.start + 1) % self.buf.len();
                 self.len -= 1;
-                self.pop_counter += 1;
+                self.front_cursor += 1;
                 Some(ptr::read(start).assume_init())
             }
         }
     }
 
+    pub fn pop_back(&mut self) -> Option<T> {
+        if self.len == 0 {
+            None
+        } else {
+            unsafe {
+                self.len -= 1;
+                let end = self.buf.as_ptr().add(self.physical_index(self.len));
+                Some(ptr::read(end).assume_init())
+            }
+        }
+    }
+
     pub fn reserve(&mut self, additional: usize) {
         let new_buf_len = self.len + additional;
         let old_buf_len = self.buf.len();
@@ -87,36 +157,146 @@ impl<T> RingVec<T> {
         }
     }
 
-    fn get_by_index(&self, index: usize) -> Option<&T> {
-        if index >= self.len {
-            None
-        } else {
-            let index = (self.start + index) % self.buf.len();
-            Some(unsafe { (self.buf[index]).assume_init_ref() })
+    /// Move the elements to a buffer of the exact length
+    pub fn shrink_to_fit(&mut self) {
+        let mut buf: Vec<MaybeUninit<T>> = Vec::with_capacity(self.len);
+        unsafe {
+            let (first, second) = self.as_slices();
+            let dst = buf.as_mut_ptr() as *mut T;
+            ptr::copy_nonoverlapping(first.as_ptr(), dst, first.len());
+            ptr::copy_nonoverlapping(second.as_ptr(), dst.add(first.len()), second.len());
+            buf.set_len(buf.capacity());
+            self.buf.set_len(0);
+        }
+        self.buf = buf;
+        self.start = 0;
+    }
+
+    /// The elements in order, split where the buffer wraps
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        if self.len == 0 {
+            return (&[], &[]);
+        }
+        let first_len = self.len.min(self.buf.len() - self.start);
+        unsafe {
+            let ptr = self.buf.as_ptr() as *const T;
+            (
+                slice::from_raw_parts(ptr.add(self.start), first_len),
+                slice::from_raw_parts(ptr, self.len - first_len),
+            )
+        }
+    }
+
+    pub fn as_mut_slices(&mut self) -> (&mut [T], &mut [T]) {
+        if self.len == 0 {
+            return (&mut [], &mut []);
+        }
+        let first_len = self.len.min(self.buf.len() - self.start);
+        unsafe {
+            let ptr = self.buf.as_mut_ptr() as *mut T;
+            (
+                slice::from_raw_parts_mut(ptr.add(self.start), first_len),
+                slice::from_raw_parts_mut(ptr, self.len - first_len),
+            )
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        let (first, second) = self.as_slices();
+        Iter {
+            first: first.iter(),
+            second: second.iter(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        let (first, second) = self.as_mut_slices();
+        IterMut {
+            first: first.iter_mut(),
+            second: second.iter_mut(),
+        }
+    }
+
+    /// Remove the elements in the index `range`, they are removed even if the iterator is not consumed.
+    /// Draining from the front keeps the cursors of the remaining elements,
+    /// otherwise the elements after `range` move to lower cursors
+    pub fn drain(&mut self, range: impl RangeBounds<usize>) -> Drain<'_, T> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len,
+        };
+        assert!(
+            start <= end && end <= self.len,
+            "drain range out of bounds. range: {}..{}, len: {}",
+            start,
+            end,
+            self.len
+        );
+
+        let count = end - start;
+        let mut drained = Vec::with_capacity(count);
+        for index in start..end {
+            let index = self.physical_index(index);
+            drained.push(unsafe { ptr::read(self.buf.as_ptr().add(index)).assume_init() });
+        }
+        if count > 0 {
+            if start == 0 {
+                self.start = self.physical_index(count);
+                self.front_cursor += count as u64;
+            } else {
+                for index in end..self.len {
+                    let from = self.physical_index(index);
+                    let to = self.physical_index(index - count);
+                    unsafe {
+                        ptr::copy_nonoverlapping(
+                            self.buf.as_ptr().add(from),
+                            self.buf.as_mut_ptr().add(to),
+                            1,
+                        );
+                    }
+                }
+            }
+            self.len -= count;
+        }
+
+        Drain {
+            iter: drained.into_iter(),
+            _phantom: PhantomData,
         }
     }
 
+    fn physical_index(&self, index: usize) -> usize {
+        (self.start + index) % self.buf.len()
+    }
+
     fn remove_to_index(&mut self, to_index: usize) {
         let to_index = to_index.min(self.len);
         if to_index == 0 {
             return;
         }
         for i in 0..to_index {
-            let i = (self.start + i) % self.buf.len();
+            let i = self.physical_index(i);
             unsafe {
                 self.buf[i].assume_init_drop();
             }
         }
-        self.start = (self.start + to_index) % self.buf.len();
+        self.start = self.physical_index(to_index);
         self.len -= to_index;
+        self.front_cursor += to_index as u64;
     }
 
     fn cursor_to_index(&self, cursor: RingCursor) -> Option<usize> {
         let cursor = cursor.cursor;
-        if cursor < self.pop_counter {
+        if cursor < self.front_cursor {
             None
         } else {
-            Some((cursor - self.pop_counter) as usize)
+            Some((cursor - self.front_cursor) as usize)
         }
     }
 }
@@ -127,7 +307,7 @@ impl<T> Default for RingVec<T> {
             buf: vec![],
             start: 0,
             len: 0,
-            pop_counter: 0,
+            front_cursor: INITIAL_FRONT_CURSOR,
         }
     }
 }
@@ -141,11 +321,56 @@ impl<T> Drop for RingVec<T> {
     }
 }
 
+impl<T> Extend<T> for RingVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for value in iter {
+            self.push_back(value);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for RingVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut ring = Self::default();
+        ring.extend(iter);
+        ring
+    }
+}
+
+impl<'r, T> IntoIterator for &'r RingVec<T> {
+    type Item = &'r T;
+    type IntoIter = Iter<'r, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'r, T> IntoIterator for &'r mut RingVec<T> {
+    type Item = &'r mut T;
+    type IntoIter = IterMut<'r, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct RingCursor {
     cursor: u64,
 }
 
+impl Sub for RingCursor {
+    type Output = u64;
+
+    /// Number of elements from `rhs` to `self`, zero if `rhs` is after `self`
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.cursor.saturating_sub(rhs.cursor)
+    }
+}
+
 impl<T> Index<RingCursor> for RingVec<T> {
     type Output = T;
 
@@ -154,6 +379,89 @@ impl<T> Index<RingCursor> for RingVec<T> {
     }
 }
 
+impl<T> IndexMut<RingCursor> for RingVec<T> {
+    fn index_mut(&mut self, index: RingCursor) -> &mut Self::Output {
+        self.get_mut_by_cursor(index).unwrap()
+    }
+}
+
+pub struct Iter<'r, T> {
+    first: slice::Iter<'r, T>,
+    second: slice::Iter<'r, T>,
+}
+
+impl<'r, T> Iterator for Iter<'r, T> {
+    type Item = &'r T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.first.next().or_else(|| self.second.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.first.len() + self.second.len();
+        (len, Some(len))
+    }
+}
+
+impl<'r, T> DoubleEndedIterator for Iter<'r, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.second.next_back().or_else(|| self.first.next_back())
+    }
+}
+
+impl<'r, T> ExactSizeIterator for Iter<'r, T> {}
+
+pub struct IterMut<'r, T> {
+    first: slice::IterMut<'r, T>,
+    second: slice::IterMut<'r, T>,
+}
+
+impl<'r, T> Iterator for IterMut<'r, T> {
+    type Item = &'r mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.first.next().or_else(|| self.second.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.first.len() + self.second.len();
+        (len, Some(len))
+    }
+}
+
+impl<'r, T> DoubleEndedIterator for IterMut<'r, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.second.next_back().or_else(|| self.first.next_back())
+    }
+}
+
+impl<'r, T> ExactSizeIterator for IterMut<'r, T> {}
+
+pub struct Drain<'r, T> {
+    iter: std::vec::IntoIter<T>,
+    _phantom: PhantomData<&'r mut RingVec<T>>,
+}
+
+impl<'r, T> Iterator for Drain<'r, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'r, T> DoubleEndedIterator for Drain<'r, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back()
+    }
+}
+
+impl<'r, T> ExactSizeIterator for Drain<'r, T> {}
+
 pub struct IterFromCursor<'r, T> {
     cur: usize,
     ring_vec: &'r RingVec<T>,
@@ -172,7 +480,7 @@ impl<'r, T> Iterator for IterFromCursor<'r, T> {
     type Item = &'r T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.ring_vec.get_by_index(self.cur) {
+        match self.ring_vec.get(self.cur) {
             None => None,
             cur @ Some(_) => {
                 self.cur += 1;
@@ -184,10 +492,12 @@ impl<'r, T> Iterator for IterFromCursor<'r, T> {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::VecDeque;
+
     use rand::distributions::{Distribution, Standard};
     use rand::Rng;
 
-    use crate::collections::ring_vec::RingVec;
+    use crate::collections::ring_vec::{RingCursor, RingVec};
 
     static mut DROP_HISTORY: Vec<i32> = vec![];
 
@@ -205,17 +515,29 @@ mod tests {
     enum RandomOp {
         Push,
         Pop,
+        PushFront,
+        PopBack,
+        Drain,
+        ShrinkToFit,
+        Mutate,
+        Iterate,
         GetCursor,
         CheckCursor,
     }
 
     impl Distribution<RandomOp> for Standard {
         fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> RandomOp {
-            match rng.next_u32() % 4 {
+            match rng.next_u32() % 10 {
                 0 => RandomOp::Push,
                 1 => RandomOp::Pop,
-                2 => RandomOp::GetCursor,
-                3 => RandomOp::CheckCursor,
+                2 => RandomOp::PushFront,
+                3 => RandomOp::PopBack,
+                4 => RandomOp::Drain,
+                5 => RandomOp::ShrinkToFit,
+                6 => RandomOp::Mutate,
+                7 => RandomOp::Iterate,
+                8 => RandomOp::GetCursor,
+                9 => RandomOp::CheckCursor,
                 _ => {
                     unreachable!()
                 }
@@ -223,48 +545,122 @@ mod tests {
         }
     }
 
+    fn values(ring: &RingVec<Info>) -> Vec<i32> {
+        ring.iter().map(|info| info.0).collect()
+    }
+
     #[test]
     fn it_works() {
         const TEST_NUM: i32 = 10000;
+        let mut rng = rand::thread_rng();
         let mut ring = RingVec::default();
-        let mut pop_cursor = 0;
-        let mut push_cursor = 0;
-        while pop_cursor < TEST_NUM {
-            match rand::random::<RandomOp>() {
+        let mut model = VecDeque::new();
+        // the cursor of the front element of `model`
+        let mut model_front = 0i64;
+        let mut cursors: Vec<(RingCursor, i64)> = vec![];
+        let mut push_counter = 0;
+        let mut drop_counter = 0;
+        while push_counter < TEST_NUM {
+            match rng.gen::<RandomOp>() {
                 RandomOp::Push => {
-                    if push_cursor < TEST_NUM {
-                        ring.push_back(Info(push_cursor));
-                        push_cursor += 1;
-                    }
+                    ring.push_back(Info(push_counter));
+                    model.push_back(push_counter);
+                    push_counter += 1;
                 }
                 RandomOp::Pop => {
-                    if pop_cursor < push_cursor {
-                        assert_eq!(ring.pop_front(), Some(Info(pop_cursor)));
-                        pop_cursor += 1;
-                    } else {
-                        assert_eq!(ring.pop_front(), None);
+                    let expected = model.pop_front();
+                    assert_eq!(ring.pop_front().map(|info| info.0), expected);
+                    if expected.is_some() {
+                        model_front += 1;
+                        drop_counter += 1;
+                    }
+                }
+                RandomOp::PushFront => {
+                    ring.push_front(Info(push_counter));
+                    model.push_front(push_counter);
+                    model_front -= 1;
+                    push_counter += 1;
+                }
+                RandomOp::PopBack => {
+                    let expected = model.pop_back();
+                    assert_eq!(ring.pop_back().map(|info| info.0), expected);
+                    if expected.is_some() {
+                        drop_counter += 1;
+                    }
+                }
+                RandomOp::Drain => {
+                    let start = rng.gen_range(0..=model.len());
+                    let end = rng.gen_range(start..=model.len().min(start + 5));
+                    let expected: Vec<_> = model.drain(start..end).collect();
+                    let drained: Vec<_> = ring.drain(start..end).map(|info| info.0).collect();
+                    assert_eq!(drained, expected);
+                    if start == 0 {
+                        model_front += (end - start) as i64;
+                    }
+                    drop_counter += (end - start) as i32;
+                }
+                RandomOp::ShrinkToFit => {
+                    if rng.gen_range(0..20) == 0 {
+                        ring.shrink_to_fit();
+                        assert_eq!(ring.capacity(), ring.len());
+                    }
+                }
+                RandomOp::Mutate => {
+                    if !model.is_empty() {
+                        let index = rng.gen_range(0..model.len());
+                        model[index] += TEST_NUM;
+                        ring.get_mut(index).unwrap().0 += TEST_NUM;
+                        for info in ring.iter_mut().rev().take(1) {
+                            info.0 += TEST_NUM;
+                        }
+                        *model.back_mut().unwrap() += TEST_NUM;
+                    }
+                }
+                RandomOp::Iterate => {
+                    assert_eq!(ring.len(), model.len());
+                    assert_eq!(ring.iter().len(), model.len());
+                    assert_eq!(values(&ring), model.iter().copied().collect::<Vec<_>>());
+                    assert!(ring
+                        .iter()
+                        .rev()
+                        .map(|info| info.0)
+                        .eq(model.iter().rev().copied()));
+                    let (first, second) = ring.as_slices();
+                    assert_eq!(first.len() + second.len(), model.len());
+                }
+                RandomOp::GetCursor => {
+                    cursors.push((ring.start_cursor(), model_front));
+                    cursors.push((ring.end_cursor(), model_front + model.len() as i64));
+                }
+                RandomOp::CheckCursor => {
+                    for (cursor, model_cursor) in &cursors {
+                        let index = model_cursor - model_front;
+                        let expected = if index < 0 {
+                            None
+                        } else {
+                            model.get(index as usize).copied()
+                        };
+                        assert_eq!(ring.get_by_cursor(*cursor).map(|info| info.0), expected);
                     }
                 }
-                RandomOp::GetCursor => {}
-                RandomOp::CheckCursor => {}
             }
         }
+        assert_eq!(unsafe { DROP_HISTORY.len() }, drop_counter as usize);
+
+        let extended: RingVec<Info> = (0..10).map(Info).collect();
+        assert_eq!(values(&extended), (0..10).collect::<Vec<_>>());
+        drop(extended);
+        ring.clear();
         assert_eq!(ring.pop_front(), None);
-        for i in 0..TEST_NUM {
-            ring.push_back(Info(i))
+        assert_eq!(ring.pop_back(), None);
+        unsafe {
+            DROP_HISTORY.clear();
         }
+        ring.extend((0..TEST_NUM).map(Info));
         drop(ring);
 
         unsafe {
-            let mut assert_drop = vec![];
-            for x in 0..TEST_NUM {
-                assert_drop.push(x);
-                assert_drop.push(x);
-            }
-            for x in 0..TEST_NUM {
-                assert_drop.push(x);
-            }
-            assert_eq!(DROP_HISTORY, assert_drop);
+            assert_eq!(DROP_HISTORY, (0..TEST_NUM).collect::<Vec<_>>());
         }
     }
 }
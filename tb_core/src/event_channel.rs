@@ -1,24 +1,79 @@
 use std::alloc::{Allocator, Global, Layout};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::ptr::NonNull;
-use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::Mutex;
 
 use crate::collections::ring_vec::{IterFromCursor, RingCursor, RingVec};
 
+/// What `EventChannel::push` does when the channel is full
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum OverflowPolicy {
+    /// Drop the oldest event, the readers which haven't read it count it as missed
+    DropOldest,
+    /// Drop the pushed event
+    DropNewest,
+    Panic,
+}
+
+/// Diagnostics of a reader
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct ReaderStats {
+    /// Number of events not read yet
+    pub lag: u64,
+    /// Number of events dropped before being read
+    pub missed: u64,
+}
+
 pub struct EventChannel<E> {
     #[cfg(debug_assertions)]
     id: u64,
-    readers: Vec<WeakReader>,
+    readers: Mutex<Vec<WeakReader>>,
     events: RingVec<E>,
+    capacity: Option<(usize, OverflowPolicy)>,
+    dropped: u64,
+    /// Events pushed concurrently, sharded by thread and merged into `events` on `flush`
+    pending: Vec<Mutex<Vec<E>>>,
+    /// Set by `push_concurrent`, so `flush` skips the shards when nothing is pending
+    has_pending: AtomicBool,
 }
 
+const PENDING_SHARDS: usize = 16;
+
 impl<E> EventChannel<E> {
-    pub fn register(&mut self) -> ReaderHandle {
+    /// A channel keeping at most `capacity` events, the overflow is handled by `policy`
+    pub fn with_capacity(capacity: usize, policy: OverflowPolicy) -> Self {
+        assert!(capacity > 0, "capacity of EventChannel must be positive");
+        Self {
+            capacity: Some((capacity, policy)),
+            ..Default::default()
+        }
+    }
+
+    /// Number of the events dropped by `OverflowPolicy::DropNewest`
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Register a reader which reads the events pushed from now on
+    pub fn register(&self) -> ReaderHandle {
+        self.register_at(self.events.end_cursor())
+    }
+
+    /// Register a reader which reads from the oldest event still kept by the channel
+    pub fn register_from_start(&self) -> ReaderHandle {
+        self.register_at(self.events.start_cursor())
+    }
+
+    fn register_at(&self, cursor: RingCursor) -> ReaderHandle {
         let reader = Box::new(Reader {
             weak_count: AtomicU8::new(2),
-            cursor: self.events.end_cursor(),
+            cursor,
+            missed: 0,
         });
         let reader = NonNull::from(Box::leak(reader));
-        self.readers.push(WeakReader(reader));
+        self.readers.lock().unwrap().push(WeakReader(reader));
         ReaderHandle {
             reader,
             #[cfg(debug_assertions)]
@@ -27,10 +82,54 @@ impl<E> EventChannel<E> {
     }
 
     pub fn push(&mut self, e: E) {
+        self.flush();
+        self.push_back(e);
+    }
+
+    /// Push from many threads at once.
+    /// The events are not visible to readers until `flush` or the next `push`
+    pub fn push_concurrent(&self, e: E) {
+        let mut hasher = DefaultHasher::new();
+        std::thread::current().id().hash(&mut hasher);
+        let shard = hasher.finish() as usize % self.pending.len();
+        self.pending[shard].lock().unwrap().push(e);
+        self.has_pending.store(true, Ordering::Release);
+    }
+
+    /// Merge the events pushed by `push_concurrent`, in order within each thread
+    pub fn flush(&mut self) {
+        if !std::mem::take(self.has_pending.get_mut()) {
+            return;
+        }
+        for shard in 0..self.pending.len() {
+            let pending = std::mem::take(self.pending[shard].get_mut().unwrap());
+            for e in pending {
+                self.push_back(e);
+            }
+        }
+    }
+
+    fn push_back(&mut self, e: E) {
         self.clean_zero_counted_reader();
-        if self.readers.is_empty() {
+        if self.readers.get_mut().unwrap().is_empty() {
             return;
         }
+        if let Some((capacity, policy)) = self.capacity {
+            if self.events.len() >= capacity {
+                match policy {
+                    OverflowPolicy::DropOldest => {
+                        self.events.pop_front();
+                    }
+                    OverflowPolicy::DropNewest => {
+                        self.dropped += 1;
+                        return;
+                    }
+                    OverflowPolicy::Panic => {
+                        panic!("EventChannel overflows. capacity: {}", capacity)
+                    }
+                }
+            }
+        }
         self.events.push_back(e);
     }
 
@@ -38,7 +137,7 @@ impl<E> EventChannel<E> {
         #[cfg(debug_assertions)]
         assert_eq!(self.id, reader.channel_id);
 
-        let cursor = &mut unsafe { reader.reader.as_mut() }.cursor;
+        let cursor = self.catch_up(reader);
         let iter = self.events.iter_from_cursor(*cursor).unwrap();
         *cursor = self.events.end_cursor();
         iter
@@ -46,18 +145,57 @@ impl<E> EventChannel<E> {
 
     pub fn read_any(&self, reader: &mut ReaderHandle) -> bool {
         let end = self.events.end_cursor();
-        let cursor = &mut unsafe { reader.reader.as_mut() }.cursor;
+        let cursor = self.catch_up(reader);
         let any = *cursor < end;
         *cursor = end;
         any
     }
 
+    /// Move the cursor of `reader` over the dropped events and count them as missed
+    fn catch_up<'h>(&self, reader: &'h mut ReaderHandle) -> &'h mut RingCursor {
+        let reader = unsafe { reader.reader.as_mut() };
+        let start = self.events.start_cursor();
+        reader.missed += start - reader.cursor;
+        if reader.cursor < start {
+            reader.cursor = start;
+        }
+        &mut reader.cursor
+    }
+
+    pub fn stats(&self, reader: &ReaderHandle) -> ReaderStats {
+        let reader = unsafe { reader.reader.as_ref() };
+        Self::reader_stats(&self.events, reader)
+    }
+
+    /// Stats of all the live readers
+    pub fn all_stats(&mut self) -> Vec<ReaderStats> {
+        let events = &self.events;
+        self.readers
+            .get_mut()
+            .unwrap()
+            .iter()
+            .map(|reader| unsafe { reader.0.as_ref() })
+            .filter(|reader| reader.weak_count.load(Ordering::Acquire) > 1)
+            .map(|reader| Self::reader_stats(events, reader))
+            .collect()
+    }
+
+    fn reader_stats(events: &RingVec<E>, reader: &Reader) -> ReaderStats {
+        let start = events.start_cursor();
+        let end = events.end_cursor();
+        ReaderStats {
+            lag: end - reader.cursor.max(start),
+            missed: reader.missed + (start - reader.cursor),
+        }
+    }
+
     fn clean_zero_counted_reader(&mut self) {
+        let readers = self.readers.get_mut().unwrap();
         let mut first: Option<RingCursor> = None;
         let mut i = 0;
         loop {
-            if i < self.readers.len() {
-                let reader = unsafe { self.readers[i].0.as_ref() };
+            if i < readers.len() {
+                let reader = unsafe { readers[i].0.as_ref() };
                 if reader.weak_count.load(Ordering::Acquire) > 1 {
                     let cursor = reader.cursor;
                     if let Some(f) = &first {
@@ -69,7 +207,7 @@ impl<E> EventChannel<E> {
                     }
                     i += 1;
                 } else {
-                    self.readers.swap_remove(i);
+                    readers.swap_remove(i);
                 }
             } else {
                 break;
@@ -93,6 +231,10 @@ impl<E> Default for EventChannel<E> {
             id: NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
             readers: Default::default(),
             events: Default::default(),
+            capacity: None,
+            dropped: 0,
+            pending: (0..PENDING_SHARDS).map(|_| Default::default()).collect(),
+            has_pending: AtomicBool::new(false),
         }
     }
 }
@@ -108,6 +250,7 @@ struct WeakReader(NonNull<Reader>);
 struct Reader {
     weak_count: AtomicU8,
     cursor: RingCursor,
+    missed: u64,
 }
 
 unsafe impl Send for ReaderHandle {}
@@ -141,7 +284,9 @@ impl Drop for WeakReader {
 
 #[cfg(test)]
 mod tests {
-    use crate::event_channel::EventChannel;
+    use rayon::prelude::*;
+
+    use crate::event_channel::{EventChannel, OverflowPolicy, ReaderStats};
 
     #[test]
     fn drop_works() {
@@ -154,4 +299,104 @@ mod tests {
         channel.push(());
         drop(channel);
     }
+
+    #[test]
+    fn push_concurrent() {
+        let mut channel = EventChannel::default();
+        let mut reader = channel.register();
+        (0..4).into_par_iter().for_each(|thread| {
+            for i in 0..100 {
+                channel.push_concurrent((thread, i));
+            }
+        });
+        assert_eq!(channel.read(&mut reader).count(), 0);
+
+        channel.flush();
+        let mut events: Vec<_> = channel.read(&mut reader).copied().collect();
+        assert_eq!(events.len(), 400);
+        events.sort();
+        assert_eq!(events[0], (0, 0));
+        assert_eq!(events[399], (3, 99));
+
+        channel.push_concurrent((4, 0));
+        channel.push((4, 1));
+        assert_eq!(
+            channel.read(&mut reader).copied().collect::<Vec<_>>(),
+            vec![(4, 0), (4, 1)]
+        );
+    }
+
+    #[test]
+    fn read_after_clean() {
+        let mut channel = EventChannel::default();
+        let mut reader0 = channel.register();
+        let mut reader1 = channel.register();
+        channel.push(0);
+        channel.push(1);
+        assert_eq!(channel.read(&mut reader0).count(), 2);
+        assert_eq!(channel.read(&mut reader1).count(), 2);
+
+        channel.push(2);
+        assert_eq!(
+            channel.read(&mut reader0).copied().collect::<Vec<_>>(),
+            vec![2]
+        );
+        channel.push(3);
+        assert_eq!(
+            channel.read(&mut reader1).copied().collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+
+        let mut reader2 = channel.register_from_start();
+        assert_eq!(
+            channel.read(&mut reader2).copied().collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(
+            channel.read(&mut reader0).copied().collect::<Vec<_>>(),
+            vec![3]
+        );
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let mut channel = EventChannel::with_capacity(3, OverflowPolicy::DropOldest);
+        let mut reader0 = channel.register();
+        let mut reader1 = channel.register();
+        for i in 0..3 {
+            channel.push(i);
+        }
+        assert_eq!(channel.read(&mut reader0).count(), 3);
+        for i in 3..5 {
+            channel.push(i);
+        }
+        assert_eq!(channel.stats(&reader0), ReaderStats { lag: 2, missed: 0 });
+        assert_eq!(channel.stats(&reader1), ReaderStats { lag: 3, missed: 2 });
+        assert_eq!(channel.all_stats().len(), 2);
+        assert_eq!(
+            channel.read(&mut reader1).copied().collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+        assert_eq!(channel.stats(&reader1), ReaderStats { lag: 0, missed: 2 });
+
+        let mut channel = EventChannel::with_capacity(2, OverflowPolicy::DropNewest);
+        let mut reader = channel.register();
+        for i in 0..4 {
+            channel.push(i);
+        }
+        assert_eq!(channel.dropped(), 2);
+        assert_eq!(
+            channel.read(&mut reader).copied().collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn overflow_panic() {
+        let mut channel = EventChannel::with_capacity(1, OverflowPolicy::Panic);
+        let _reader = channel.register();
+        channel.push(0);
+        channel.push(1);
+    }
 }
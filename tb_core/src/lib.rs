@@ -8,9 +8,11 @@ pub mod algorithm;
 pub mod collections;
 pub mod error;
 pub mod event_channel;
+pub mod hash;
 pub mod path_util;
 
 pub mod serde {
+    pub use rmp_serde;
     pub use serde::*;
     pub use serde_box::*;
     pub use serde_json;
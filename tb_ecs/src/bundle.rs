@@ -0,0 +1,133 @@
+use crate::registry::ComponentIndex;
+use crate::{Commands, Component, ComponentMask, Entities, Entity, World};
+
+/// A tuple of components which are inserted into an entity together
+pub trait ComponentBundle: 'static + Send {
+    fn component_indices() -> Vec<ComponentIndex>;
+
+    fn insert_storages(world: &mut World);
+
+    /// Reserve capacity for `additional` more entities in the storages of the components
+    ///
+    /// # Safety
+    ///
+    /// see `World::fetch_mut`
+    unsafe fn reserve(world: &World, additional: usize);
+
+    /// Insert the components into the storages without moving `entity` between archetypes
+    ///
+    /// # Safety
+    ///
+    /// see `World::fetch_mut`
+    unsafe fn insert(self, world: &World, entity: Entity, commands: &mut Commands);
+}
+
+macro_rules! impl_component_bundle {
+    ($c:ident) => {
+        impl_component_bundle!(@impl $c);
+    };
+    ($c0:ident, $($c1:ident), +) => {
+        impl_component_bundle!($($c1), +);
+        impl_component_bundle!(@impl $c0, $($c1), +);
+    };
+    (@impl $($c:ident), +) => {
+        impl<$($c: Component), +> ComponentBundle for ($($c,)+) {
+            fn component_indices() -> Vec<ComponentIndex> {
+                vec![$(ComponentIndex::get::<$c>()), +]
+            }
+
+            fn insert_storages(world: &mut World) {
+                $(world.insert_components::<$c>();)+
+            }
+
+            unsafe fn reserve(world: &World, additional: usize) {
+                $(world.fetch_components_mut::<$c>().reserve(additional);)+
+            }
+
+            #[allow(non_snake_case)]
+            unsafe fn insert(self, world: &World, entity: Entity, commands: &mut Commands) {
+                let ($($c,)+) = self;
+                $(
+                    let storage = world.fetch_components_mut::<$c>();
+                    storage.insert(entity, $c);
+                    if let Some(component) = storage.get(entity) {
+                        component.on_add(entity, commands);
+                    }
+                )+
+            }
+        }
+    };
+}
+
+impl_component_bundle!(C0, C1, C2, C3, C4, C5, C6, C7);
+
+impl World {
+    /// Create an entity for each bundle of components.
+    /// The entities are put into their archetype at once instead of one transfer per component
+    pub fn spawn_batch<B: ComponentBundle>(
+        &mut self,
+        bundles: impl IntoIterator<Item = B>,
+    ) -> Vec<Entity> {
+        let bundles: Vec<B> = bundles.into_iter().collect();
+        B::insert_storages(self);
+        let mut mask = ComponentMask::default();
+        for component_index in B::component_indices() {
+            mask.insert(*component_index);
+        }
+        let entities = self
+            .insert(Entities::default)
+            .new_entities_with(mask, bundles.len());
+
+        let mut commands = Commands::default();
+        unsafe {
+            B::reserve(self, bundles.len());
+            for (bundle, &entity) in bundles.into_iter().zip(&entities) {
+                bundle.insert(self, entity, &mut commands);
+            }
+        }
+        self.defer(&mut commands);
+        self.maintain();
+        entities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[component]
+    struct Particle {
+        x: f32,
+    }
+
+    #[component]
+    struct Velocity {
+        x: f32,
+    }
+
+    #[test]
+    fn spawn_batch() {
+        let mut world = World::default();
+        world.create_entity().with(Particle { x: -1.0 }).create();
+        let entities =
+            world.spawn_batch((0..100).map(|i| (Particle { x: i as f32 }, Velocity { x: 1.0 })));
+        assert_eq!(entities.len(), 100);
+        world.spawn_batch(vec![(Velocity { x: 2.0 },)]);
+
+        let all = unsafe { world.fetch::<Entities>() };
+        assert_eq!(all.iter().count(), 102);
+        let particles = unsafe { world.fetch_components::<Particle>() };
+        assert_eq!(particles.get(entities[10]).unwrap().x, 10.0);
+
+        let particles = unsafe { RAWComponents::<Particle>::fetch(&world) };
+        let velocities = unsafe { RAWComponents::<Velocity>::fetch(&world) };
+        assert_eq!((&particles, &velocities).join().count(), 100);
+        assert_eq!((&particles).join().count(), 101);
+        assert_eq!((&velocities).join().count(), 101);
+
+        world.kill(entities[0]);
+        let particles = unsafe { RAWComponents::<Particle>::fetch(&world) };
+        let velocities = unsafe { RAWComponents::<Velocity>::fetch(&world) };
+        assert_eq!((&particles, &velocities).join().count(), 99);
+    }
+}
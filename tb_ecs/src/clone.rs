@@ -0,0 +1,151 @@
+use crate::registry::ComponentRegistry;
+use crate::{Commands, ComponentMask, Entities, Entity, LocalToWorldLink, World};
+
+impl World {
+    /// Create a new entity with clones of all the components of `entity`.
+    /// Returns `None` if `entity` is not alive
+    pub fn clone_entity(&mut self, entity: Entity) -> Option<Entity> {
+        let sources = component_masks(self, &[entity]);
+        let link = unsafe { copy_entities_unchecked(self, self, sources) };
+        self.maintain();
+        link.get(entity)
+    }
+
+    /// Copy `entities` of `other` with clones of all their components.
+    /// References among the copied entities are remapped to the copies, other references are kept.
+    /// Returns the link from the entities of `other` to the copies
+    pub fn copy_entities_from(&mut self, other: &World, entities: &[Entity]) -> LocalToWorldLink {
+        self.insert(Entities::default);
+        let sources = component_masks(other, entities);
+        for (_, mask) in &sources {
+            for component_index in mask.iter() {
+                let (operation, _registry) = ComponentRegistry::operation(component_index.into());
+                operation.insert_storage(self);
+            }
+        }
+        let link = unsafe { copy_entities_unchecked(other, self, sources) };
+        self.maintain();
+        link
+    }
+}
+
+fn component_masks(world: &World, entities: &[Entity]) -> Vec<(Entity, ComponentMask)> {
+    match unsafe { world.try_fetch::<Entities>() } {
+        Err(_) => vec![],
+        Ok(world_entities) => entities
+            .iter()
+            .filter_map(|&entity| {
+                world_entities
+                    .component_mask(entity)
+                    .map(|mask| (entity, mask))
+            })
+            .collect(),
+    }
+}
+
+/// The storages of the components in `sources` must exist in `to`
+unsafe fn copy_entities_unchecked(
+    from: &World,
+    to: &World,
+    sources: Vec<(Entity, ComponentMask)>,
+) -> LocalToWorldLink {
+    let mut link = LocalToWorldLink::default();
+    let entities = to.fetch::<Entities>();
+    for (entity, mask) in &sources {
+        link.insert(*entity, entities.new_entity_with(mask.clone()));
+    }
+
+    let mut commands = Commands::default();
+    for (entity, mask) in &sources {
+        let copy = link.get(*entity).unwrap();
+        for component_index in mask.iter() {
+            let (operation, _registry) = ComponentRegistry::operation(component_index.into());
+            operation.clone_component(from, *entity, to, copy, &link, &mut commands);
+        }
+    }
+    to.defer(&mut commands);
+    link
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[component]
+    struct Name {
+        value: String,
+    }
+
+    #[component]
+    struct Links {
+        target: Entity,
+        others: Vec<Entity>,
+    }
+
+    #[test]
+    fn clone_entity() {
+        let mut world = World::default();
+        let entity0 = world
+            .create_entity()
+            .with(Name { value: "a".into() })
+            .create();
+        let entity1 = world
+            .create_entity()
+            .with(Name { value: "b".into() })
+            .with(Links {
+                target: entity0,
+                others: vec![],
+            })
+            .create();
+
+        let copy = world.clone_entity(entity1).unwrap();
+        assert_ne!(copy, entity1);
+        let names = unsafe { world.fetch_components::<Name>() };
+        assert_eq!(names.get(copy).unwrap().value, "b");
+        let links = unsafe { world.fetch_components::<Links>() };
+        assert_eq!(links.get(copy).unwrap().target, entity0);
+
+        let names = unsafe { RAWComponents::<Name>::fetch(&world) };
+        let links = unsafe { RAWComponents::<Links>::fetch(&world) };
+        assert_eq!((&names, &links).join().count(), 2);
+        assert_eq!((&names).join().count(), 3);
+
+        world.kill(entity1);
+        assert!(world.clone_entity(entity1).is_none());
+    }
+
+    #[test]
+    fn copy_entities_from() {
+        let mut other = World::default();
+        let outside = other.create_entity().create();
+        let entity0 = other
+            .create_entity()
+            .with(Name { value: "a".into() })
+            .create();
+        let entity1 = other
+            .create_entity()
+            .with(Links {
+                target: entity0,
+                others: vec![outside],
+            })
+            .create();
+
+        let mut world = World::default();
+        world.create_entity().create();
+        let link = world.copy_entities_from(&other, &[entity0, entity1]);
+        let copy0 = link.get(entity0).unwrap();
+        let copy1 = link.get(entity1).unwrap();
+
+        let entities = unsafe { world.fetch::<Entities>() };
+        assert_eq!(entities.iter().count(), 3);
+        let names = unsafe { world.fetch_components::<Name>() };
+        assert_eq!(names.get(copy0).unwrap().value, "a");
+        let links = unsafe { world.fetch_components::<Links>() };
+        let links = links.get(copy1).unwrap();
+        assert_eq!(links.target, copy0);
+        assert_eq!(links.others, vec![outside]);
+
+        let links = unsafe { RAWComponents::<Links>::fetch(&world) };
+        assert_eq!((&links).join().count(), 1);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{Component, ComponentStorage, Entity, SystemData, World, WriteComponents};
+
+type Command = Box<dyn FnOnce(&mut World) + Send>;
+
+/// Operations on the world which are deferred until the buffer is applied.
+#[derive(Default)]
+pub struct Commands {
+    commands: Vec<Command>,
+}
+
+impl Commands {
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn push(&mut self, command: impl 'static + FnOnce(&mut World) + Send) {
+        self.commands.push(Box::new(command));
+    }
+
+    pub fn kill(&mut self, entity: Entity) {
+        self.push(move |world| world.kill(entity));
+    }
+
+    pub fn insert_component<C: Component>(&mut self, entity: Entity, component: C) {
+        self.push(move |world| world.insert_component(entity, component));
+    }
+
+    pub fn remove_component<C: Component>(&mut self, entity: Entity) {
+        self.push(move |world| world.remove_component::<C>(entity));
+    }
+
+    pub fn append(&mut self, other: &mut Commands) {
+        self.commands.append(&mut other.commands);
+    }
+
+    pub fn apply(&mut self, world: &mut World) {
+        for command in std::mem::take(&mut self.commands) {
+            command(world);
+        }
+    }
+}
+
+impl World {
+    pub fn insert_component<C: Component>(&mut self, entity: Entity, component: C) {
+        self.insert_components::<C>();
+        let mut components = unsafe { WriteComponents::<C>::fetch(self) };
+        components.insert(entity, component);
+        self.maintain();
+    }
+
+    pub fn remove_component<C: Component>(&mut self, entity: Entity) {
+        if !self.contains::<ComponentStorage<C>>() {
+            return;
+        }
+        let mut components = unsafe { WriteComponents::<C>::fetch(self) };
+        components.remove(entity);
+        self.maintain();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[component(name = "CommandComp")]
+    struct Comp {}
+
+    #[test]
+    fn apply_commands() {
+        let mut world = World::default();
+        let entity0 = world.create_entity().create();
+        let entity1 = world.create_entity().create();
+
+        let mut commands = Commands::default();
+        commands.kill(entity0);
+        commands.insert_component(entity1, Comp {});
+        assert_eq!(commands.len(), 2);
+        commands.apply(&mut world);
+        assert!(commands.is_empty());
+
+        let entities = unsafe { world.fetch::<Entities>() };
+        assert!(!entities.is_alive(entity0));
+        assert!(unsafe { world.fetch_components::<Comp>() }.contains(entity1));
+
+        let mut commands = Commands::default();
+        commands.remove_component::<Comp>(entity1);
+        commands.apply(&mut world);
+        assert!(!unsafe { world.fetch_components::<Comp>() }.contains(entity1));
+    }
+}
@@ -2,6 +2,7 @@ use std::marker::PhantomData;
 use std::ops::Not;
 
 pub use anti_components::*;
+pub use reflect::*;
 pub use registry::*;
 pub use storage::*;
 pub use tb_core::serde::*;
@@ -9,11 +10,25 @@ pub use tb_core::serde::*;
 use crate::*;
 
 mod anti_components;
+mod reflect;
 pub(crate) mod registry;
 mod storage;
 
 #[serde_box]
-pub trait Component: 'static + Send + Sync + SerdeBoxSer + SerdeBoxDe {}
+pub trait Component: 'static + Send + Sync + SerdeBoxSer + SerdeBoxDe {
+    /// Called after the component is added to an entity which didn't have it
+    fn on_add(&self, _entity: Entity, _commands: &mut Commands) {}
+
+    /// Called before the component is removed from an entity, including when the entity is killed
+    fn on_remove(&self, _entity: Entity, _commands: &mut Commands) {}
+
+    /// Called on the old value after it was replaced by a new one
+    fn on_replace(&self, _entity: Entity, _commands: &mut Commands) {}
+
+    /// Visit all the entities referenced by the component.
+    /// Generated by `#[component]` for the `Entity` and `Vec<Entity>` fields
+    fn for_each_entity_ref(&mut self, _action: &mut dyn FnMut(&mut Entity)) {}
+}
 
 pub trait EntityRef {
     fn for_each(&mut self, action: &mut impl FnMut(&mut Entity));
@@ -25,6 +40,7 @@ pub trait ComponentWithEntityRef<'e>: Component {
 }
 
 pub struct Components<'r, S: 'r + Storage, C: Component, A: AccessOrder> {
+    world: &'r World,
     entities: &'r Entities,
     storage: S,
     _phantom: PhantomData<(C, A)>,
@@ -145,9 +161,8 @@ impl<'r, C: Component> Join<'r> for &'r mut WriteComponents<'r, C> {
     type Element = C;
     type ElementFetcher = &'r mut ComponentStorage<C>;
 
-    fn open(self) -> (Box<dyn 'r + Iterator<Item = Entity>>, Self::ElementFetcher) {
-        let storage = unsafe { &mut *(&mut self.storage as *mut _ as *mut _) };
-        (self.get_matched_entities(), storage)
+    fn open(mut self) -> (Box<dyn 'r + Iterator<Item = Entity>>, Self::ElementFetcher) {
+        (self.get_matched_entities(), self.elem_fetcher())
     }
 
     fn entities(&self) -> &'r Entities {
@@ -177,6 +192,7 @@ impl<'r, C: Component> Join<'r> for &'r mut WriteComponents<'r, C> {
 impl<'r, C: Component, A: AccessOrder> ReadComponents<'r, C, A> {
     unsafe fn new(world: &'r World) -> Self {
         Self {
+            world,
             entities: world.fetch(),
             storage: world.fetch_components::<C>(),
             _phantom: Default::default(),
@@ -187,14 +203,43 @@ impl<'r, C: Component, A: AccessOrder> ReadComponents<'r, C, A> {
 impl<'r, C: Component> WriteComponents<'r, C> {
     unsafe fn new(world: &'r World) -> Self {
         Self {
+            world,
             entities: world.fetch(),
             storage: world.fetch_components_mut::<C>(),
             _phantom: Default::default(),
         }
     }
+
+    /// Insert or replace the component of `entity`.
+    /// Commands queued by the hooks are deferred to `World::maintain`.
     pub fn insert(&mut self, entity: Entity, component: C) {
-        self.storage.insert(entity, component);
-        self.entities.on_component_inserted::<C>(entity);
+        let mut commands = Commands::default();
+        match self.storage.insert(entity, component) {
+            None => {
+                self.entities.on_component_inserted::<C>(entity);
+                self.storage
+                    .get(entity)
+                    .unwrap()
+                    .on_add(entity, &mut commands);
+            }
+            Some(replaced) => replaced.on_replace(entity, &mut commands),
+        }
+        self.world.defer(&mut commands);
+    }
+
+    /// Remove the component of `entity`.
+    /// Commands queued by the hooks are deferred to `World::maintain`.
+    pub fn remove(&mut self, entity: Entity) {
+        let mut commands = Commands::default();
+        match self.storage.get(entity) {
+            None => {
+                return;
+            }
+            Some(component) => component.on_remove(entity, &mut commands),
+        }
+        self.storage.remove(entity);
+        self.entities.on_component_removed::<C>(entity);
+        self.world.defer(&mut commands);
     }
 }
 
@@ -270,7 +315,7 @@ mod tests {
     use crate::component::storage::ComponentStorage;
     use crate::*;
 
-    #[component]
+    #[component(name = "StorageComponent1")]
     struct Component1 {
         value1: i32,
     }
@@ -280,6 +325,21 @@ mod tests {
         value2: i32,
     }
 
+    #[component(on_add = Hooked::on_added, on_remove = Hooked::on_removed)]
+    struct Hooked {
+        other: Entity,
+    }
+
+    impl Hooked {
+        fn on_added(&self, entity: Entity, commands: &mut Commands) {
+            commands.insert_component(entity, Component1 { value1: 100 });
+        }
+
+        fn on_removed(&self, _entity: Entity, commands: &mut Commands) {
+            commands.kill(self.other);
+        }
+    }
+
     #[test]
     fn it_works() {
         let mut world = World::default();
@@ -352,4 +412,36 @@ mod tests {
             assert_eq!(component1.value1, 10);
         }
     }
+
+    #[test]
+    fn component_hooks() {
+        let mut world = World::default();
+        let other = world.create_entity().create();
+        let entity = world.create_entity().with(Hooked { other }).create();
+        let components1 = unsafe { world.fetch_components::<Component1>() };
+        assert_eq!(components1.get(entity).unwrap().value1, 100);
+
+        world.remove_component::<Hooked>(entity);
+        assert!(!unsafe { world.fetch::<Entities>() }.is_alive(other));
+    }
+
+    #[test]
+    fn on_add_hook_by_commands_and_json() {
+        let mut world = World::default();
+        let other = world.create_entity().create();
+        let entity = world.create_entity().create();
+        let mut commands = Commands::default();
+        commands.insert_component(entity, Hooked { other });
+        commands.apply(&mut world);
+        let components1 = unsafe { world.fetch_components::<Component1>() };
+        assert_eq!(components1.get(entity).unwrap().value1, 100);
+
+        let entity = world.create_entity().create();
+        ComponentRegistry::get_by_name(Hooked::type_name())
+            .unwrap()
+            .insert_json(&mut world, entity, serde_json::json!({ "other": other }))
+            .unwrap();
+        let components1 = unsafe { world.fetch_components::<Component1>() };
+        assert_eq!(components1.get(entity).unwrap().value1, 100);
+    }
 }
@@ -0,0 +1,223 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tb_core::serde::serde_json::{self, Value};
+
+use crate::{ComponentInfo, ComponentRegistry, Entity, World};
+
+pub use errors::{Error as ReflectError, ErrorKind as ReflectErrorKind, Result as ReflectResult};
+
+mod errors {
+    pub use tb_core::error::*;
+
+    use crate::Entity;
+
+    error_chain! {
+        foreign_links {
+            Json(tb_core::serde::serde_json::Error);
+        }
+
+        errors {
+            FieldNotFound(component: String, field: String) {
+                description("Failed to find field"),
+                display("Failed to find field. component: {}, field: {}", component, field),
+            }
+            ComponentNotFound(component: String, entity: Entity) {
+                description("Failed to find component"),
+                display("Failed to find component. component: {}, entity: {:?}", component, entity),
+            }
+            ComponentNotRegistered(component: String) {
+                description("Component is not registered"),
+                display("Component is not registered. component: {}", component),
+            }
+            DuplicatedComponentName(component: String, registered: String, other: String) {
+                description("Component name is used by more than one component type"),
+                display("Component name is used by more than one component type. component: {}, types: {} and {}", component, registered, other),
+            }
+            EntityNotAlive(entity: Entity) {
+                description("Entity is not alive"),
+                display("Entity is not alive. entity: {:?}", entity),
+            }
+        }
+    }
+}
+
+/// Runtime type information of a component, generated by `#[component]`
+pub trait Reflect {
+    /// The registered name, the type name unless set by `#[component(name = "...")]`
+    fn type_name() -> &'static str
+    where
+        Self: Sized;
+    fn fields() -> Vec<FieldInfo>
+    where
+        Self: Sized;
+    fn get_field(&self, name: &str) -> ReflectResult<Value>;
+    fn set_field(&mut self, name: &str, value: Value) -> ReflectResult<()>;
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FieldInfo {
+    name: &'static str,
+    type_name: &'static str,
+}
+
+impl FieldInfo {
+    pub fn new<T>(name: &'static str) -> Self {
+        Self {
+            name,
+            type_name: std::any::type_name::<T>(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub fn get<T: Serialize>(field: &T) -> ReflectResult<Value> {
+        Ok(serde_json::to_value(field)?)
+    }
+
+    pub fn set<T: DeserializeOwned>(field: &mut T, value: Value) -> ReflectResult<()> {
+        *field = serde_json::from_value(value)?;
+        Ok(())
+    }
+
+    pub fn not_found<C: Reflect>(name: &str) -> ReflectError {
+        ReflectErrorKind::FieldNotFound(C::type_name().into(), name.into()).into()
+    }
+}
+
+impl World {
+    /// Serialize the component named `component` of `entity`
+    pub fn get_component_json(&self, entity: Entity, component: &str) -> ReflectResult<Value> {
+        unsafe { Self::component_info(component)?.get_json(self, entity) }
+    }
+
+    /// Replace the existing component named `component` of `entity`
+    pub fn set_component_json(
+        &mut self,
+        entity: Entity,
+        component: &str,
+        value: Value,
+    ) -> ReflectResult<()> {
+        Self::component_info(component)?.set_json(self, entity, value)
+    }
+
+    /// Insert or replace the component named `component` of `entity`
+    pub fn insert_component_json(
+        &mut self,
+        entity: Entity,
+        component: &str,
+        value: Value,
+    ) -> ReflectResult<()> {
+        Self::component_info(component)?.insert_json(self, entity, value)
+    }
+
+    pub(crate) fn component_info(component: &str) -> ReflectResult<&'static ComponentInfo> {
+        ComponentRegistry::get_by_name(component)
+            .ok_or_else(|| ReflectErrorKind::ComponentNotRegistered(component.into()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tb_core::serde::serde_json::json;
+
+    use crate::*;
+
+    #[component]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+
+    #[component(name = "reflect_tests::Tuple")]
+    struct Tuple(i32, String);
+
+    #[test]
+    fn reflect_fields() {
+        assert_eq!(Position::type_name(), "Position");
+        let fields: Vec<_> = Position::fields()
+            .iter()
+            .map(|field| (field.name(), field.type_name()))
+            .collect();
+        assert_eq!(fields, vec![("x", "f32"), ("y", "f32")]);
+
+        let mut position = Position { x: 1.0, y: 2.0 };
+        assert_eq!(position.get_field("y").unwrap(), json!(2.0));
+        position.set_field("x", json!(10.0)).unwrap();
+        assert_eq!(position.x, 10.0);
+        assert!(position.get_field("z").is_err());
+        assert!(position.set_field("y", json!("not a number")).is_err());
+
+        assert_eq!(Tuple::type_name(), "reflect_tests::Tuple");
+        let mut tuple = Tuple(1, "a".into());
+        assert_eq!(Tuple::fields()[1].name(), "1");
+        tuple.set_field("1", json!("b")).unwrap();
+        assert_eq!(tuple.get_field("1").unwrap(), json!("b"));
+    }
+
+    #[test]
+    fn lookup_by_name() {
+        let info = ComponentRegistry::get_by_name(Position::type_name()).unwrap();
+        assert_eq!(info.name(), Position::type_name());
+        assert_eq!(info.fields().len(), 2);
+
+        let mut world = World::default();
+        let entity = world
+            .create_entity()
+            .with(Position { x: 1.0, y: 2.0 })
+            .create();
+        unsafe {
+            assert_eq!(info.get_field(&world, entity, "x").unwrap(), json!(1.0));
+            info.set_field(&world, entity, "x", json!(3.0)).unwrap();
+            assert_eq!(
+                world.fetch_components::<Position>().get(entity).unwrap().x,
+                3.0
+            );
+        }
+
+        let mut names = vec![];
+        ComponentRegistry::for_each(|info| names.push(info.name()));
+        assert!(names.contains(&Position::type_name()));
+        assert!(names.contains(&Tuple::type_name()));
+    }
+
+    #[test]
+    fn component_json() {
+        let mut world = World::default();
+        let entity = world.create_entity().create();
+        assert!(world.get_component_json(entity, "Position").is_err());
+        assert!(world
+            .set_component_json(entity, "Position", json!({"x": 1.0, "y": 2.0}))
+            .is_err());
+
+        world
+            .insert_component_json(entity, "Position", json!({"x": 1.0, "y": 2.0}))
+            .unwrap();
+        assert_eq!(
+            world.get_component_json(entity, "Position").unwrap(),
+            json!({"x": 1.0, "y": 2.0})
+        );
+        let positions = unsafe { RAWComponents::<Position>::fetch(&world) };
+        assert_eq!((&positions).join().count(), 1);
+
+        world
+            .set_component_json(entity, "Position", json!({"x": 3.0, "y": 4.0}))
+            .unwrap();
+        let positions = unsafe { world.fetch_components::<Position>() };
+        assert_eq!(positions.get(entity).unwrap().y, 4.0);
+
+        assert!(world
+            .set_component_json(entity, "Position", json!({"x": 3.0}))
+            .is_err());
+        assert!(world.get_component_json(entity, "Unknown").is_err());
+        world.kill(entity);
+        assert!(world
+            .insert_component_json(entity, "Position", json!({"x": 1.0, "y": 2.0}))
+            .is_err());
+    }
+}
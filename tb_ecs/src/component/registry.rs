@@ -6,7 +6,14 @@ use std::marker::PhantomData;
 use std::ops::{Deref, Index};
 use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
-use crate::{Component, Entity, World};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tb_core::serde::serde_json::{self, Value};
+
+use crate::{
+    Commands, Component, ComponentStorage, Entities, Entity, FieldInfo, LocalToWorldLink, Reflect,
+    ReflectErrorKind, ReflectResult, World,
+};
 
 #[derive(Default, Copy, Clone, Eq, PartialEq, Debug, Hash)]
 pub struct ComponentIndex(usize);
@@ -55,25 +62,21 @@ impl ComponentTypeId {
 pub struct ComponentRegistry {
     infos: Vec<&'static ComponentInfo>,
     type_id_to_index: HashMap<ComponentTypeId, ComponentIndex>,
+    name_to_index: HashMap<&'static str, ComponentIndex>,
 }
 
 impl ComponentRegistry {
-    pub fn add_component_infos(component_infos: Box<dyn Iterator<Item = &'static ComponentInfo>>) {
+    /// Register `component_infos`, nothing is registered if one of their names is already used by another component type
+    pub fn add_component_infos(
+        component_infos: Box<dyn Iterator<Item = &'static ComponentInfo>>,
+    ) -> ReflectResult<()> {
+        let component_infos: Vec<_> = component_infos.collect();
         let cr: &mut ComponentRegistry = &mut Self::write();
-        let infos = &mut cr.infos;
-        let type_id_to_index = &mut cr.type_id_to_index;
+        cr.check_names(&component_infos)?;
         for info in component_infos {
-            match type_id_to_index.entry(info.type_id) {
-                Entry::Occupied(occupied) => {
-                    let index = unsafe { infos.get_unchecked_mut(**occupied.get()) };
-                    *index = info;
-                }
-                Entry::Vacant(vacant) => {
-                    vacant.insert(ComponentIndex(infos.len()));
-                    infos.push(info);
-                }
-            }
+            cr.add_component_info(info);
         }
+        Ok(())
     }
 
     pub fn for_each(op: impl FnMut(&&ComponentInfo)) {
@@ -82,6 +85,59 @@ impl ComponentRegistry {
         this.infos.iter().for_each(op);
     }
 
+    pub fn infos() -> Vec<&'static ComponentInfo> {
+        Self::read().infos.clone()
+    }
+
+    pub fn get_by_name(name: &str) -> Option<&'static ComponentInfo> {
+        let this = Self::read();
+        this.name_to_index
+            .get(name)
+            .map(|index| this.infos[**index])
+    }
+
+    fn check_names(&self, infos: &[&'static ComponentInfo]) -> ReflectResult<()> {
+        let mut names: HashMap<&str, &ComponentInfo> = HashMap::new();
+        for &info in infos {
+            let registered = match names.get(info.name) {
+                Some(registered) => Some(*registered),
+                None => self
+                    .name_to_index
+                    .get(info.name)
+                    .map(|index| self.infos[**index]),
+            };
+            if let Some(registered) = registered {
+                if registered.type_id != info.type_id {
+                    return Err(ReflectErrorKind::DuplicatedComponentName(
+                        info.name.into(),
+                        registered.rust_type_name.into(),
+                        info.rust_type_name.into(),
+                    )
+                    .into());
+                }
+            }
+            names.insert(info.name, info);
+        }
+        Ok(())
+    }
+
+    fn add_component_info(&mut self, info: &'static ComponentInfo) {
+        let index = match self.type_id_to_index.entry(info.type_id) {
+            Entry::Occupied(occupied) => {
+                let index = *occupied.get();
+                self.infos[*index] = info;
+                index
+            }
+            Entry::Vacant(vacant) => {
+                let index = ComponentIndex(self.infos.len());
+                vacant.insert(index);
+                self.infos.push(info);
+                index
+            }
+        };
+        self.name_to_index.insert(info.name, index);
+    }
+
     pub(crate) fn operation(
         component_index: ComponentIndex,
     ) -> (
@@ -98,13 +154,18 @@ impl ComponentRegistry {
             let mut instance = ComponentRegistry {
                 infos: vec![],
                 type_id_to_index: Default::default(),
+                name_to_index: Default::default(),
             };
 
-            for info in inventory::iter::<ComponentInfo> {
-                instance
-                    .type_id_to_index
-                    .insert(info.type_id, ComponentIndex(instance.infos.len()));
-                instance.infos.push(info);
+            let infos: Vec<_> = inventory::iter::<ComponentInfo>.into_iter().collect();
+            if let Err(error) = instance.check_names(&infos) {
+                panic!(
+                    "{}, set another one with #[component(name = \"...\")]",
+                    error
+                );
+            }
+            for info in infos {
+                instance.add_component_info(info);
             }
             RwLock::new(instance)
         });
@@ -122,6 +183,43 @@ impl ComponentRegistry {
 
 pub trait ComponentOperation: Send + Sync {
     unsafe fn remove_from_world(&self, world: &World, entity: Entity);
+    unsafe fn on_remove(&self, world: &World, entity: Entity, commands: &mut Commands);
+    unsafe fn get_field(&self, world: &World, entity: Entity, field: &str) -> ReflectResult<Value>;
+    unsafe fn set_field(
+        &self,
+        world: &World,
+        entity: Entity,
+        field: &str,
+        value: Value,
+    ) -> ReflectResult<()>;
+    unsafe fn get_json(&self, world: &World, entity: Entity) -> ReflectResult<Value>;
+    fn set_json(&self, world: &mut World, entity: Entity, value: Value) -> ReflectResult<()>;
+    fn insert_json(&self, world: &mut World, entity: Entity, value: Value) -> ReflectResult<()>;
+    unsafe fn to_json_entries(&self, world: &World) -> ReflectResult<Vec<(Entity, Value)>>;
+    fn insert_linked_json(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        value: Value,
+        link: &LocalToWorldLink,
+    ) -> ReflectResult<()>;
+    unsafe fn get_linked_json(
+        &self,
+        world: &World,
+        entity: Entity,
+        link: &LocalToWorldLink,
+    ) -> ReflectResult<Value>;
+    fn remove(&self, world: &mut World, entity: Entity);
+    fn insert_storage(&self, world: &mut World);
+    unsafe fn clone_component(
+        &self,
+        from: &World,
+        from_entity: Entity,
+        to: &World,
+        to_entity: Entity,
+        link: &LocalToWorldLink,
+        commands: &mut Commands,
+    );
 }
 
 struct Operation<C: Component> {
@@ -132,26 +230,288 @@ unsafe impl<C: Component> Send for Operation<C> {}
 
 unsafe impl<C: Component> Sync for Operation<C> {}
 
-impl<C: Component> ComponentOperation for Operation<C> {
+impl<C: Component + Clone + Reflect + Serialize + DeserializeOwned> Operation<C> {
+    fn component_not_found(entity: Entity) -> ReflectErrorKind {
+        ReflectErrorKind::ComponentNotFound(C::type_name().into(), entity)
+    }
+
+    fn insert_if_alive(world: &mut World, entity: Entity, component: C) -> ReflectResult<()> {
+        let is_alive = unsafe { world.try_fetch::<Entities>() }
+            .map(|entities| entities.is_alive(entity))
+            .unwrap_or(false);
+        if !is_alive {
+            return Err(ReflectErrorKind::EntityNotAlive(entity).into());
+        }
+        world.insert_component(entity, component);
+        Ok(())
+    }
+
+    unsafe fn get(world: &World, entity: Entity) -> Option<&C> {
+        world
+            .try_fetch::<ComponentStorage<C>>()
+            .ok()
+            .and_then(|storage| storage.get(entity))
+    }
+}
+
+impl<C: Component + Clone + Reflect + Serialize + DeserializeOwned> ComponentOperation
+    for Operation<C>
+{
     unsafe fn remove_from_world(&self, world: &World, entity: Entity) {
         world.fetch_components_mut::<C>().remove(entity)
     }
+
+    unsafe fn on_remove(&self, world: &World, entity: Entity, commands: &mut Commands) {
+        if let Some(component) = world.fetch_components::<C>().get(entity) {
+            component.on_remove(entity, commands)
+        }
+    }
+
+    unsafe fn get_field(&self, world: &World, entity: Entity, field: &str) -> ReflectResult<Value> {
+        match Self::get(world, entity) {
+            None => Err(Self::component_not_found(entity).into()),
+            Some(component) => component.get_field(field),
+        }
+    }
+
+    unsafe fn set_field(
+        &self,
+        world: &World,
+        entity: Entity,
+        field: &str,
+        value: Value,
+    ) -> ReflectResult<()> {
+        match world
+            .try_fetch_mut::<ComponentStorage<C>>()
+            .ok()
+            .and_then(|storage| storage.get_mut(entity))
+        {
+            None => Err(Self::component_not_found(entity).into()),
+            Some(component) => component.set_field(field, value),
+        }
+    }
+
+    unsafe fn get_json(&self, world: &World, entity: Entity) -> ReflectResult<Value> {
+        match Self::get(world, entity) {
+            None => Err(Self::component_not_found(entity).into()),
+            Some(component) => Ok(serde_json::to_value(component)?),
+        }
+    }
+
+    fn set_json(&self, world: &mut World, entity: Entity, value: Value) -> ReflectResult<()> {
+        if unsafe { Self::get(world, entity) }.is_none() {
+            return Err(Self::component_not_found(entity).into());
+        }
+        self.insert_json(world, entity, value)
+    }
+
+    fn insert_json(&self, world: &mut World, entity: Entity, value: Value) -> ReflectResult<()> {
+        let component: C = serde_json::from_value(value)?;
+        Self::insert_if_alive(world, entity, component)
+    }
+
+    unsafe fn to_json_entries(&self, world: &World) -> ReflectResult<Vec<(Entity, Value)>> {
+        match world.try_fetch::<ComponentStorage<C>>() {
+            Err(_) => Ok(vec![]),
+            Ok(storage) => storage
+                .iter()
+                .map(|(entity, component)| Ok((entity, serde_json::to_value(component)?)))
+                .collect(),
+        }
+    }
+
+    fn insert_linked_json(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        value: Value,
+        link: &LocalToWorldLink,
+    ) -> ReflectResult<()> {
+        let mut component: C = serde_json::from_value(value)?;
+        let entities = world.insert(Entities::default);
+        component.for_each_entity_ref(&mut |e: &mut Entity| {
+            *e = link.get_or_dangling(*e, entities);
+        });
+        Self::insert_if_alive(world, entity, component)
+    }
+
+    unsafe fn get_linked_json(
+        &self,
+        world: &World,
+        entity: Entity,
+        link: &LocalToWorldLink,
+    ) -> ReflectResult<Value> {
+        match Self::get(world, entity) {
+            None => Err(Self::component_not_found(entity).into()),
+            Some(component) => {
+                let value = serde_json::to_value(component)?;
+                if link.is_empty() {
+                    return Ok(value);
+                }
+                let mut component: C = serde_json::from_value(value)?;
+                component.for_each_entity_ref(&mut |e: &mut Entity| {
+                    *e = link.get(*e).unwrap_or(*e);
+                });
+                Ok(serde_json::to_value(component)?)
+            }
+        }
+    }
+
+    fn remove(&self, world: &mut World, entity: Entity) {
+        world.remove_component::<C>(entity)
+    }
+
+    fn insert_storage(&self, world: &mut World) {
+        world.insert_components::<C>();
+    }
+
+    unsafe fn clone_component(
+        &self,
+        from: &World,
+        from_entity: Entity,
+        to: &World,
+        to_entity: Entity,
+        link: &LocalToWorldLink,
+        commands: &mut Commands,
+    ) {
+        let mut component = match Self::get(from, from_entity) {
+            None => return,
+            Some(component) => component.clone(),
+        };
+        component.for_each_entity_ref(&mut |e: &mut Entity| {
+            *e = link.get(*e).unwrap_or(*e);
+        });
+        let storage = to.fetch_components_mut::<C>();
+        storage.insert(to_entity, component);
+        if let Some(component) = storage.get(to_entity) {
+            component.on_add(to_entity, commands);
+        }
+    }
 }
 
 pub struct ComponentInfo {
     type_id: ComponentTypeId,
+    rust_type_name: &'static str,
+    name: &'static str,
+    fields: Vec<FieldInfo>,
     operation: Box<dyn ComponentOperation>,
 }
 
 impl ComponentInfo {
-    pub fn new<C: Component>() -> Self {
+    pub fn new<C: Component + Clone + Reflect + Serialize + DeserializeOwned>() -> Self {
         Self {
             type_id: ComponentTypeId::new::<C>(),
+            rust_type_name: std::any::type_name::<C>(),
+            name: C::type_name(),
+            fields: C::fields(),
             operation: Box::new(Operation::<C> {
                 _phantom: Default::default(),
             }),
         }
     }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn fields(&self) -> &[FieldInfo] {
+        &self.fields
+    }
+
+    /// Get the field of the component of `entity` by name
+    ///
+    /// # Safety
+    ///
+    /// see `World::fetch`
+    pub unsafe fn get_field(
+        &self,
+        world: &World,
+        entity: Entity,
+        field: &str,
+    ) -> ReflectResult<Value> {
+        self.operation.get_field(world, entity, field)
+    }
+
+    /// Set the field of the component of `entity` by name
+    ///
+    /// # Safety
+    ///
+    /// see `World::fetch_mut`
+    pub unsafe fn set_field(
+        &self,
+        world: &World,
+        entity: Entity,
+        field: &str,
+        value: Value,
+    ) -> ReflectResult<()> {
+        self.operation.set_field(world, entity, field, value)
+    }
+
+    /// Serialize the component of `entity`
+    ///
+    /// # Safety
+    ///
+    /// see `World::fetch`
+    pub unsafe fn get_json(&self, world: &World, entity: Entity) -> ReflectResult<Value> {
+        self.operation.get_json(world, entity)
+    }
+
+    /// Replace the existing component of `entity` by a deserialized one
+    pub fn set_json(&self, world: &mut World, entity: Entity, value: Value) -> ReflectResult<()> {
+        self.operation.set_json(world, entity, value)
+    }
+
+    /// Serialize the components of all entities
+    ///
+    /// # Safety
+    ///
+    /// see `World::fetch`
+    pub unsafe fn to_json_entries(&self, world: &World) -> ReflectResult<Vec<(Entity, Value)>> {
+        self.operation.to_json_entries(world)
+    }
+
+    /// Insert or replace the component of `entity` by a deserialized one
+    pub fn insert_json(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        value: Value,
+    ) -> ReflectResult<()> {
+        self.operation.insert_json(world, entity, value)
+    }
+
+    /// Insert or replace the component of `entity` by a deserialized one,
+    /// the entities it references are converted through `link`, unlinked ones stay dangling
+    pub fn insert_linked_json(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        value: Value,
+        link: &LocalToWorldLink,
+    ) -> ReflectResult<()> {
+        self.operation
+            .insert_linked_json(world, entity, value, link)
+    }
+
+    /// Serialize the component of `entity`,
+    /// the entities it references are converted through `link` if linked
+    ///
+    /// # Safety
+    ///
+    /// see `World::fetch`
+    pub unsafe fn get_linked_json(
+        &self,
+        world: &World,
+        entity: Entity,
+        link: &LocalToWorldLink,
+    ) -> ReflectResult<Value> {
+        self.operation.get_linked_json(world, entity, link)
+    }
+
+    /// Remove the component of `entity` if it has one
+    pub fn remove(&self, world: &mut World, entity: Entity) {
+        self.operation.remove(world, entity)
+    }
 }
 
 inventory::collect!(ComponentInfo);
@@ -160,7 +520,7 @@ inventory::collect!(ComponentInfo);
 mod tests {
     use std::thread;
 
-    use crate::registry::ComponentIndex;
+    use crate::registry::{ComponentIndex, ComponentRegistry};
     use crate::*;
 
     #[component]
@@ -187,4 +547,43 @@ mod tests {
             join.join().unwrap()
         }
     }
+
+    #[test]
+    fn default_name() {
+        assert_eq!(Component1::type_name(), "Component1");
+        assert_eq!(
+            ComponentRegistry::get_by_name(Component1::type_name())
+                .unwrap()
+                .name(),
+            Component1::type_name()
+        );
+    }
+
+    #[test]
+    fn duplicated_name() {
+        let mut registry = ComponentRegistry {
+            infos: vec![],
+            type_id_to_index: Default::default(),
+            name_to_index: Default::default(),
+        };
+        let mut info = ComponentInfo::new::<Component1>();
+        info.name = Component0::type_name();
+        let info: &'static ComponentInfo = Box::leak(Box::new(info));
+        let info0: &'static ComponentInfo = Box::leak(Box::new(ComponentInfo::new::<Component0>()));
+        registry.add_component_info(info0);
+        match registry.check_names(&[info]) {
+            Err(ReflectError(ReflectErrorKind::DuplicatedComponentName(name, _, _), _)) => {
+                assert_eq!(name, Component0::type_name())
+            }
+            _ => panic!("registering a duplicated name should fail"),
+        }
+        assert!(registry.check_names(&[info0]).is_ok());
+
+        let empty = ComponentRegistry {
+            infos: vec![],
+            type_id_to_index: Default::default(),
+            name_to_index: Default::default(),
+        };
+        assert!(empty.check_names(&[info0, info]).is_err());
+    }
 }
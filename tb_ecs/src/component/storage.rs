@@ -32,13 +32,18 @@ impl<T: Component> ComponentStorage<T> {
         self.components.is_empty()
     }
 
-    pub fn insert(&mut self, entity: Entity, elem: T) {
+    /// Insert `elem` for `entity`, returns the replaced one if there is
+    pub fn insert(&mut self, entity: Entity, elem: T) -> Option<T> {
         match self.entity_to_index.entry(entity) {
-            Entry::Occupied(occupied) => self.components[*occupied.get()] = elem,
+            Entry::Occupied(occupied) => Some(std::mem::replace(
+                &mut self.components[*occupied.get()],
+                elem,
+            )),
             Entry::Vacant(vacant) => {
                 vacant.insert(self.components.len());
                 self.components.push(elem);
                 self.entities.push(entity);
+                None
             }
         }
     }
@@ -54,6 +59,16 @@ impl<T: Component> ComponentStorage<T> {
         }
     }
 
+    pub fn reserve(&mut self, additional: usize) {
+        self.components.reserve(additional);
+        self.entities.reserve(additional);
+        self.entity_to_index.reserve(additional);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.entities.iter().copied().zip(self.components.iter())
+    }
+
     pub fn get(&self, entity: Entity) -> Option<&T> {
         self.entity_to_index
             .get(&entity)
@@ -99,6 +114,30 @@ impl<'s, T: Component> join::ElementFetcher for &'s mut ComponentStorage<T> {
 pub struct LocalToWorldLink(HashMap<Entity, Entity>);
 
 impl LocalToWorldLink {
+    pub fn get(&self, local: Entity) -> Option<Entity> {
+        self.0.get(&local).copied()
+    }
+
+    pub fn insert(&mut self, local: Entity, world: Entity) {
+        self.0.insert(local, world);
+    }
+
+    pub fn remove(&mut self, local: Entity) -> Option<Entity> {
+        self.0.remove(&local)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.0.iter().map(|(&local, &world)| (local, world))
+    }
+
     pub fn build_link(&mut self, local: Entity, entities: &Entities) -> Entity {
         match self.0.get(&local) {
             None => {
@@ -109,6 +148,14 @@ impl LocalToWorldLink {
             Some(entity) => *entity,
         }
     }
+
+    /// The entity linked to `local`, a reference to an unlinked entity stays dangling
+    pub fn get_or_dangling(&self, local: Entity, entities: &Entities) -> Entity {
+        match self.0.get(&local) {
+            None => entities.dangling(local),
+            Some(entity) => *entity,
+        }
+    }
 }
 
 pub trait ConvertToWorld {
@@ -141,6 +188,9 @@ impl EntityToIndex {
     pub(crate) fn remove(&mut self, entity: &Entity) -> Option<usize> {
         self.entity_to_index.remove(entity)
     }
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.entity_to_index.reserve(additional)
+    }
     pub(crate) fn entry(&mut self, entity: Entity) -> Entry<'_, Entity, usize> {
         self.entity_to_index.entry(entity)
     }
@@ -0,0 +1,559 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use tb_core::serde::serde_json::Value;
+
+pub use errors::{Error as DiffError, ErrorKind as DiffErrorKind, Result as DiffResult};
+
+use crate::{
+    Entities, Entity, LocalToWorldLink, ReflectError, ReflectErrorKind, World, WorldSnapshot,
+};
+
+mod errors {
+    pub use tb_core::error::*;
+
+    error_chain! {
+        links {
+            Reflect(crate::ReflectError, crate::ReflectErrorKind);
+            Snapshot(crate::SnapshotError, crate::SnapshotErrorKind);
+        }
+
+        errors {
+            InvalidPatchPath(path: String) {
+                description("Invalid patch path"),
+                display("Invalid patch path. path: {}", path),
+            }
+        }
+    }
+}
+
+/// A JSON patch operation, `path` is a JSON pointer into a component value
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+}
+
+impl PatchOperation {
+    /// The operations turning `before` into `after`
+    pub fn diff(before: &Value, after: &Value) -> Vec<PatchOperation> {
+        let mut patch = vec![];
+        Self::diff_at("", before, after, &mut patch);
+        patch
+    }
+
+    fn diff_at(path: &str, before: &Value, after: &Value, patch: &mut Vec<PatchOperation>) {
+        match (before, after) {
+            (Value::Object(before), Value::Object(after)) => {
+                for key in before.keys().filter(|key| !after.contains_key(*key)) {
+                    patch.push(PatchOperation::Remove {
+                        path: child_path(path, key),
+                    });
+                }
+                for (key, value) in after {
+                    match before.get(key) {
+                        None => patch.push(PatchOperation::Add {
+                            path: child_path(path, key),
+                            value: value.clone(),
+                        }),
+                        Some(old) => Self::diff_at(&child_path(path, key), old, value, patch),
+                    }
+                }
+            }
+            (Value::Array(before), Value::Array(after)) => {
+                let common = before.len().min(after.len());
+                for i in 0..common {
+                    Self::diff_at(&format!("{}/{}", path, i), &before[i], &after[i], patch);
+                }
+                for i in (common..before.len()).rev() {
+                    patch.push(PatchOperation::Remove {
+                        path: format!("{}/{}", path, i),
+                    });
+                }
+                for (i, value) in after.iter().enumerate().skip(common) {
+                    patch.push(PatchOperation::Add {
+                        path: format!("{}/{}", path, i),
+                        value: value.clone(),
+                    });
+                }
+            }
+            _ if before != after => patch.push(PatchOperation::Replace {
+                path: path.into(),
+                value: after.clone(),
+            }),
+            _ => {}
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        match self {
+            PatchOperation::Add { path, .. } => path,
+            PatchOperation::Remove { path } => path,
+            PatchOperation::Replace { path, .. } => path,
+        }
+    }
+
+    pub fn apply(&self, target: &mut Value) -> DiffResult<()> {
+        match self {
+            PatchOperation::Replace { path, value } => {
+                *target.pointer_mut(path).ok_or_else(|| invalid_path(path))? = value.clone();
+            }
+            PatchOperation::Add { path, value } => {
+                let (parent, key) = split_path(path)?;
+                match target.pointer_mut(parent) {
+                    Some(Value::Object(map)) => {
+                        map.insert(key, value.clone());
+                    }
+                    Some(Value::Array(array)) => {
+                        let index = if key == "-" {
+                            array.len()
+                        } else {
+                            parse_index(path, &key, array.len() + 1)?
+                        };
+                        array.insert(index, value.clone());
+                    }
+                    _ => return Err(invalid_path(path)),
+                }
+            }
+            PatchOperation::Remove { path } => {
+                let (parent, key) = split_path(path)?;
+                match target.pointer_mut(parent) {
+                    Some(Value::Object(map)) => {
+                        map.remove(key.as_str()).ok_or_else(|| invalid_path(path))?;
+                    }
+                    Some(Value::Array(array)) => {
+                        let index = parse_index(path, &key, array.len())?;
+                        array.remove(index);
+                    }
+                    _ => return Err(invalid_path(path)),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"))
+}
+
+fn split_path(path: &str) -> DiffResult<(&str, String)> {
+    let index = path.rfind('/').ok_or_else(|| invalid_path(path))?;
+    let key = path[index + 1..].replace("~1", "/").replace("~0", "~");
+    Ok((&path[..index], key))
+}
+
+fn parse_index(path: &str, key: &str, len: usize) -> DiffResult<usize> {
+    key.parse()
+        .ok()
+        .filter(|index| *index < len)
+        .ok_or_else(|| invalid_path(path))
+}
+
+fn invalid_path(path: &str) -> DiffError {
+    DiffErrorKind::InvalidPatchPath(path.into()).into()
+}
+
+/// Difference between two world states, keyed by the registered component names.
+/// Components of created entities are listed as added,
+/// components of killed entities are not listed as removed
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct WorldDiff {
+    created: Vec<Entity>,
+    killed: Vec<Entity>,
+    added: BTreeMap<String, Vec<(Entity, Value)>>,
+    removed: BTreeMap<String, Vec<Entity>>,
+    changed: BTreeMap<String, Vec<(Entity, Vec<PatchOperation>)>>,
+}
+
+impl WorldDiff {
+    pub fn between(before: &WorldSnapshot, after: &WorldSnapshot) -> Self {
+        let before_entities: HashSet<Entity> = before.entities().iter().copied().collect();
+        let after_entities: HashSet<Entity> = after.entities().iter().copied().collect();
+        let mut diff = WorldDiff {
+            created: after
+                .entities()
+                .iter()
+                .filter(|entity| !before_entities.contains(entity))
+                .copied()
+                .collect(),
+            killed: before
+                .entities()
+                .iter()
+                .filter(|entity| !after_entities.contains(entity))
+                .copied()
+                .collect(),
+            ..Default::default()
+        };
+
+        let empty = vec![];
+        let names: BTreeSet<&String> = before
+            .components()
+            .keys()
+            .chain(after.components().keys())
+            .collect();
+        for name in names {
+            let old_entries = before.components().get(name).unwrap_or(&empty);
+            let new_entries = after.components().get(name).unwrap_or(&empty);
+            let old_values: HashMap<Entity, &Value> =
+                old_entries.iter().map(|(e, value)| (*e, value)).collect();
+            let new_entities: HashSet<Entity> = new_entries.iter().map(|(e, _)| *e).collect();
+
+            let mut added = vec![];
+            let mut changed = vec![];
+            for (entity, value) in new_entries {
+                match old_values.get(entity) {
+                    None => added.push((*entity, value.clone())),
+                    Some(old) => {
+                        let patch = PatchOperation::diff(old, value);
+                        if !patch.is_empty() {
+                            changed.push((*entity, patch));
+                        }
+                    }
+                }
+            }
+            let removed: Vec<Entity> = old_entries
+                .iter()
+                .map(|(e, _)| *e)
+                .filter(|e| !new_entities.contains(e) && after_entities.contains(e))
+                .collect();
+
+            if !added.is_empty() {
+                diff.added.insert(name.clone(), added);
+            }
+            if !removed.is_empty() {
+                diff.removed.insert(name.clone(), removed);
+            }
+            if !changed.is_empty() {
+                diff.changed.insert(name.clone(), changed);
+            }
+        }
+        diff
+    }
+
+    pub fn created(&self) -> &[Entity] {
+        &self.created
+    }
+
+    pub fn killed(&self) -> &[Entity] {
+        &self.killed
+    }
+
+    pub fn added(&self) -> &BTreeMap<String, Vec<(Entity, Value)>> {
+        &self.added
+    }
+
+    pub fn removed(&self) -> &BTreeMap<String, Vec<Entity>> {
+        &self.removed
+    }
+
+    pub fn changed(&self) -> &BTreeMap<String, Vec<(Entity, Vec<PatchOperation>)>> {
+        &self.changed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty()
+            && self.killed.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+
+    /// Names of the components which are added, removed or changed
+    pub fn touched_components(&self) -> BTreeSet<&str> {
+        self.added
+            .keys()
+            .chain(self.removed.keys())
+            .chain(self.changed.keys())
+            .map(|name| name.as_str())
+            .collect()
+    }
+
+    /// Apply the diff to `world`.
+    /// `link` maps the entities of the diff to the ones of `world`, unlinked live entities map to themselves.
+    /// Created entities are linked to the new ones, killed entities are unlinked.
+    /// References to entities which are neither linked nor created stay dangling.
+    /// Names, entities and patches are resolved first, so `world` and `link` are left untouched if one of them fails
+    pub fn apply(&self, world: &mut World, link: &mut LocalToWorldLink) -> DiffResult<()> {
+        let entities = world.insert(Entities::default);
+        let mut working = link.clone();
+        let linked: HashSet<Entity> = working.iter().map(|(_, entity)| entity).collect();
+        for entity in entities.iter() {
+            if working.get(entity).is_none() && !linked.contains(&entity) {
+                working.insert(entity, entity);
+            }
+        }
+        let killed: Vec<Entity> = self
+            .killed
+            .iter()
+            .filter_map(|&local| working.remove(local))
+            .collect();
+
+        let created: HashSet<Entity> = self.created.iter().copied().collect();
+        let check = |local: Entity| {
+            if created.contains(&local) || working.get(local).is_some() {
+                Ok(())
+            } else {
+                Err(ReflectError::from(ReflectErrorKind::EntityNotAlive(local)))
+            }
+        };
+
+        let mut removed = Vec::with_capacity(self.removed.len());
+        for (name, entities) in &self.removed {
+            entities.iter().try_for_each(|&local| check(local))?;
+            removed.push((World::component_info(name)?, entities));
+        }
+
+        let mut added = Vec::with_capacity(self.added.len());
+        for (name, entries) in &self.added {
+            entries.iter().try_for_each(|(local, _)| check(*local))?;
+            added.push((World::component_info(name)?, entries));
+        }
+
+        let mut world_to_local = LocalToWorldLink::default();
+        for (local, entity) in working.iter() {
+            world_to_local.insert(entity, local);
+        }
+        let mut changed = Vec::with_capacity(self.changed.len());
+        for (name, entries) in &self.changed {
+            let info = World::component_info(name)?;
+            for (local, patch) in entries {
+                let entity = working
+                    .get(*local)
+                    .ok_or_else(|| ReflectError::from(ReflectErrorKind::EntityNotAlive(*local)))?;
+                let mut value = unsafe { info.get_linked_json(world, entity, &world_to_local) }?;
+                for operation in patch {
+                    operation.apply(&mut value)?;
+                }
+                changed.push((info, *local, value));
+            }
+        }
+
+        let entities = unsafe { world.fetch::<Entities>() };
+        for &local in &self.created {
+            let entity = entities.new_entity();
+            working.insert(local, entity);
+            link.insert(local, entity);
+        }
+        for entity in killed {
+            world.kill(entity);
+        }
+        for local in &self.killed {
+            link.remove(*local);
+        }
+
+        for (info, entities) in removed {
+            for &local in entities {
+                info.remove(world, working.get(local).unwrap());
+            }
+        }
+        for (info, entries) in added {
+            for (local, value) in entries {
+                let entity = working.get(*local).unwrap();
+                info.insert_linked_json(world, entity, value.clone(), &working)?;
+            }
+        }
+        for (info, local, value) in changed {
+            info.insert_linked_json(world, working.get(local).unwrap(), value, &working)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl World {
+    /// Difference from the state of this world to the state of `other`
+    pub fn diff(&self, other: &World) -> DiffResult<WorldDiff> {
+        Ok(WorldDiff::between(&self.snapshot()?, &other.snapshot()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use tb_core::serde::serde_json::{self, json};
+
+    use crate::*;
+
+    #[component]
+    struct Score {
+        value: i32,
+        history: Vec<i32>,
+    }
+
+    #[component]
+    struct Follow {
+        target: Entity,
+    }
+
+    #[test]
+    fn json_patch() {
+        let before = json!({"a": 1, "b": [1, 2, 3], "c": {"d/e": true}, "f": "x"});
+        let after = json!({"a": 2, "b": [1, 4], "c": {"d/e": true, "g": null}, "h": [1]});
+        let patch = PatchOperation::diff(&before, &after);
+        let json = serde_json::to_string(&patch).unwrap();
+        let patch: Vec<PatchOperation> = serde_json::from_str(&json).unwrap();
+
+        let mut value = before.clone();
+        for operation in &patch {
+            operation.apply(&mut value).unwrap();
+        }
+        assert_eq!(value, after);
+        assert!(PatchOperation::diff(&after, &after).is_empty());
+
+        let invalid = PatchOperation::Remove {
+            path: "/b/5".into(),
+        };
+        assert!(invalid.apply(&mut value).is_err());
+    }
+
+    #[test]
+    fn diff_and_apply() {
+        let mut world = World::default();
+        let entity0 = world
+            .create_entity()
+            .with(Score {
+                value: 1,
+                history: vec![],
+            })
+            .create();
+        let entity1 = world
+            .create_entity()
+            .with(Score {
+                value: 2,
+                history: vec![1],
+            })
+            .with(Follow { target: entity0 })
+            .create();
+        let entity2 = world.create_entity().create();
+        let before = world.snapshot().unwrap();
+
+        let mut other = World::default();
+        let mut link = other.restore(&before).unwrap();
+
+        unsafe { world.fetch_components_mut::<Score>() }
+            .get_mut(entity1)
+            .unwrap()
+            .history
+            .push(2);
+        world.remove_component::<Follow>(entity1);
+        world.kill(entity2);
+        let entity3 = world
+            .create_entity()
+            .with(Follow { target: entity0 })
+            .create();
+        let after = world.snapshot().unwrap();
+
+        let diff = WorldDiff::between(&before, &after);
+        assert_eq!(diff.created(), &[entity3]);
+        assert_eq!(diff.killed(), &[entity2]);
+        assert_eq!(
+            diff.added()["Follow"],
+            vec![(entity3, json!({"target": entity0}))]
+        );
+        assert_eq!(diff.removed()["Follow"], vec![entity1]);
+        assert_eq!(diff.changed()["Score"].len(), 1);
+        assert_eq!(
+            diff.touched_components().into_iter().collect::<Vec<_>>(),
+            vec!["Follow", "Score"]
+        );
+        assert!(WorldDiff::between(&after, &after).is_empty());
+
+        let json = serde_json::to_string(&diff).unwrap();
+        let diff: WorldDiff = serde_json::from_str(&json).unwrap();
+        diff.apply(&mut other, &mut link).unwrap();
+
+        let entities = unsafe { other.fetch::<Entities>() };
+        assert_eq!(entities.iter().count(), 3);
+        assert!(link.get(entity2).is_none());
+        let new_entity0 = link.get(entity0).unwrap();
+        let new_entity1 = link.get(entity1).unwrap();
+        let new_entity3 = link.get(entity3).unwrap();
+        assert!(entities.is_alive(new_entity3));
+
+        let scores = unsafe { other.fetch_components::<Score>() };
+        assert_eq!(scores.get(new_entity1).unwrap().history, vec![1, 2]);
+        let follows = unsafe { other.fetch_components::<Follow>() };
+        assert!(!follows.contains(new_entity1));
+        assert_eq!(follows.get(new_entity3).unwrap().target, new_entity0);
+
+        assert!(world.diff(&world).unwrap().is_empty());
+        let mut identity = LocalToWorldLink::default();
+        WorldDiff::between(&after, &before)
+            .apply(&mut world, &mut identity)
+            .unwrap();
+        let scores = unsafe { world.fetch_components::<Score>() };
+        assert_eq!(scores.get(entity1).unwrap().history, vec![1]);
+        let follows = unsafe { world.fetch_components::<Follow>() };
+        assert_eq!(follows.get(entity1).unwrap().target, entity0);
+        assert!(!follows.contains(entity3));
+        let entities = unsafe { world.fetch::<Entities>() };
+        assert!(!entities.is_alive(entity3));
+        assert!(entities.is_alive(identity.get(entity2).unwrap()));
+    }
+
+    #[test]
+    fn apply_dangling_reference() {
+        let mut world = World::default();
+        world.create_entity().create();
+        let before = world.snapshot().unwrap();
+        let mut other = World::default();
+        let mut link = other.restore(&before).unwrap();
+
+        let outside = world.create_entity().create();
+        let entity = world
+            .create_entity()
+            .with(Follow { target: outside })
+            .create();
+        world.kill(outside);
+        let diff = WorldDiff::between(&before, &world.snapshot().unwrap());
+        assert_eq!(diff.created(), &[entity]);
+
+        diff.apply(&mut other, &mut link).unwrap();
+        let entities = unsafe { other.fetch::<Entities>() };
+        assert_eq!(entities.iter().count(), 2);
+        assert!(link.get(outside).is_none());
+        let follows = unsafe { other.fetch_components::<Follow>() };
+        let target = follows.get(link.get(entity).unwrap()).unwrap().target;
+        assert!(!entities.is_alive(target));
+    }
+
+    #[test]
+    fn apply_failure_leaves_world() {
+        let mut world = World::default();
+        let entity0 = world.create_entity().create();
+        let entity1 = world
+            .create_entity()
+            .with(Score {
+                value: 1,
+                history: vec![],
+            })
+            .create();
+        let before = world.snapshot().unwrap();
+        let mut other = World::default();
+        let mut link = other.restore(&before).unwrap();
+        let linked = |link: &LocalToWorldLink| link.iter().collect::<HashSet<_>>();
+        let original = linked(&link);
+
+        world.kill(entity0);
+        world.create_entity().create();
+        unsafe { world.fetch_components_mut::<Score>() }
+            .get_mut(entity1)
+            .unwrap()
+            .value = 2;
+        let mut diff = WorldDiff::between(&before, &world.snapshot().unwrap());
+        diff.added
+            .insert("NotRegistered".into(), vec![(entity1, json!({}))]);
+
+        assert!(diff.apply(&mut other, &mut link).is_err());
+        assert_eq!(linked(&link), original);
+        let entities = unsafe { other.fetch::<Entities>() };
+        assert_eq!(entities.iter().count(), 2);
+        assert!(entities.is_alive(link.get(entity0).unwrap()));
+        let scores = unsafe { other.fetch_components::<Score>() };
+        assert_eq!(scores.get(link.get(entity1).unwrap()).unwrap().value, 1);
+    }
+}
@@ -11,7 +11,7 @@ use bit_set::BitSet;
 use serde::{Deserialize, Serialize};
 
 use crate::registry::{ComponentIndex, ComponentRegistry};
-use crate::{Component, Join, SystemData, World, WriteComponents};
+use crate::{Commands, Component, Join, SystemData, World, WriteComponents};
 
 #[derive(Deserialize, Serialize, Copy, Clone, Hash, PartialEq, Eq, Debug)]
 pub struct Entity {
@@ -78,18 +78,40 @@ impl Entities {
             .unwrap()
             .on_component_inserted(entity, component_index);
     }
+    pub(crate) fn component_mask(&self, entity: Entity) -> Option<ComponentMask> {
+        self.read().component_mask(entity)
+    }
+    pub(crate) fn new_entity_with(&self, mask: ComponentMask) -> Entity {
+        self.write().new_entity_with(mask)
+    }
+    pub(crate) fn new_entities_with(&self, mask: ComponentMask, count: usize) -> Vec<Entity> {
+        self.write().new_entities_with(mask, count)
+    }
+    pub(crate) fn dangling(&self, entity: Entity) -> Entity {
+        self.write().dangling(entity)
+    }
+    pub(crate) fn on_component_removed<C: Component>(&self, entity: Entity) {
+        let component_index = ComponentIndex::get::<C>();
+        self.inner
+            .write()
+            .unwrap()
+            .on_component_removed(entity, component_index);
+    }
 }
 
 impl World {
     pub fn kill(&mut self, entity: Entity) {
         let entities = unsafe { self.fetch::<Entities>() };
+        let mut commands = Commands::default();
         unsafe {
             entities.kill(entity, |component_index| {
-                ComponentRegistry::operation(component_index.into())
-                    .0
-                    .remove_from_world(self, entity)
+                let (operation, _registry) = ComponentRegistry::operation(component_index.into());
+                operation.on_remove(self, entity, &mut commands);
+                operation.remove_from_world(self, entity)
             });
         }
+        self.defer(&mut commands);
+        self.maintain();
     }
 }
 
@@ -171,6 +193,29 @@ impl EntitiesInner {
         self.transfer(entity, entity_index, next_archetype);
     }
 
+    fn on_component_removed(&mut self, entity: Entity, component_index: ComponentIndex) {
+        let entity_index = match self.entity_to_index.get(&entity).copied() {
+            Some(index) => index,
+            None => {
+                return;
+            }
+        };
+
+        let next_archetype = self.archetypes_remove_to_next[entity_index.archetype]
+            .get(&component_index)
+            .copied();
+        let next_archetype = next_archetype.unwrap_or_else(|| {
+            let mut next_mask = self.archetypes_component_mask[entity_index.archetype].clone();
+            next_mask.remove(*component_index);
+            let next_archetype = self.get_or_insert_archetype(next_mask);
+            self.archetypes_remove_to_next[entity_index.archetype]
+                .insert(component_index, next_archetype);
+            next_archetype
+        });
+
+        self.transfer(entity, entity_index, next_archetype);
+    }
+
     fn transfer(&mut self, entity: Entity, from: EntityIndex, to: ArchetypeIndex) {
         let from_entities = &mut self.archetypes_entities[from.archetype];
         let from_last = *from_entities.last().unwrap();
@@ -186,17 +231,55 @@ impl EntitiesInner {
         self.len
     }
 
+    fn component_mask(&self, entity: Entity) -> Option<ComponentMask> {
+        self.entity_to_index
+            .get(&entity)
+            .map(|index| self.archetypes_component_mask[index.archetype].clone())
+    }
+
+    /// `entity` if it is dead and its id is never reused, otherwise a new id which is never alive
+    fn dangling(&mut self, entity: Entity) -> Entity {
+        if entity.id < self.next_id && !self.is_alive(entity) {
+            return entity;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        Entity { id }
+    }
+
     pub fn new_entity(&mut self) -> Entity {
+        self.new_entity_with(ComponentMask::default())
+    }
+
+    /// Create an entity directly in the archetype of `mask`,
+    /// the components must be inserted into the storages by the caller
+    fn new_entity_with(&mut self, mask: ComponentMask) -> Entity {
         let id = self.next_id;
         self.next_id += 1;
         let entity = Entity { id };
-        let archetype = self.get_or_insert_archetype(ComponentMask::default());
+        let archetype = self.get_or_insert_archetype(mask);
         let new_entity_index = self.push_entity(archetype, entity);
         self.entity_to_index.insert(entity, new_entity_index);
         self.len += 1;
         entity
     }
 
+    fn new_entities_with(&mut self, mask: ComponentMask, count: usize) -> Vec<Entity> {
+        let archetype = self.get_or_insert_archetype(mask);
+        self.archetypes_entities[archetype].reserve(count);
+        self.entity_to_index.reserve(count);
+        (0..count)
+            .map(|_| {
+                let entity = Entity { id: self.next_id };
+                self.next_id += 1;
+                let entity_index = self.push_entity(archetype, entity);
+                self.entity_to_index.insert(entity, entity_index);
+                self.len += 1;
+                entity
+            })
+            .collect()
+    }
+
     fn push_entity(&mut self, archetype: ArchetypeIndex, entity: Entity) -> EntityIndex {
         let entities = &mut self.archetypes_entities[archetype];
         let index_in_archetype = entities.len();
@@ -327,6 +410,7 @@ impl EntityCreator<'_> {
     }
     pub fn create(&mut self) -> Entity {
         self.created = true;
+        self.world.maintain();
         self.entity
     }
 }
@@ -0,0 +1,355 @@
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+use tb_core::collections::ring_vec::IterFromCursor;
+use tb_core::event_channel::{EventChannel, ReaderHandle};
+
+pub use tb_core::event_channel::{OverflowPolicy, ReaderStats};
+
+use crate::world::ResourceId;
+use crate::{Flush, SystemData, World, Write, RAW};
+
+/// Queue of events of type `E`. Insert it into the world to use `EventWriter` and `EventReader`.
+/// Events are kept until every registered reader has read them.
+/// The events sent before the first reader is registered are kept until the first `flush`,
+/// which `World::maintain` does at the end of every frame once the events are accessed by a system,
+/// so the events nobody reads are dropped instead of piling up
+pub struct Events<E> {
+    channel: EventChannel<E>,
+    /// Keeps the events sent before the first reader is registered, released by `flush`
+    keep_alive: Mutex<Option<ReaderHandle>>,
+}
+
+impl<E> Default for Events<E> {
+    fn default() -> Self {
+        Self::from_channel(EventChannel::default())
+    }
+}
+
+impl<E> Events<E> {
+    /// Keep at most `capacity` events, the overflow is handled by `policy`
+    pub fn with_capacity(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self::from_channel(EventChannel::with_capacity(capacity, policy))
+    }
+
+    fn from_channel(channel: EventChannel<E>) -> Self {
+        let keep_alive = Mutex::new(Some(channel.register()));
+        Self {
+            channel,
+            keep_alive,
+        }
+    }
+
+    pub fn send(&mut self, event: E) {
+        self.channel.push(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = E>) {
+        for event in events {
+            self.channel.push(event);
+        }
+    }
+
+    /// Send through a shared reference, the event is readable after the next `flush`
+    pub fn send_concurrent(&self, event: E) {
+        self.channel.push_concurrent(event);
+    }
+
+    /// Register a reader which starts from the oldest event still kept
+    pub fn register(&self) -> ReaderHandle {
+        let mut keep_alive = self.keep_alive.lock().unwrap();
+        let reader = self.channel.register_from_start();
+        keep_alive.take();
+        reader
+    }
+
+    /// Read the events sent since the last read of `reader`,
+    /// `reader` is registered on its first read
+    pub fn read(&self, reader: &mut ReaderId<E>) -> IterFromCursor<'_, E> {
+        let handle = reader.handle.get_or_insert_with(|| self.register());
+        self.channel.read(handle)
+    }
+
+    /// Merge the events sent by `send_concurrent`,
+    /// and stop keeping the events for the readers not registered yet
+    pub fn flush(&mut self) {
+        self.channel.flush();
+        self.keep_alive.get_mut().unwrap().take();
+    }
+
+    /// Lag and missed events of `reader`, zero before its first read
+    pub fn stats(&self, reader: &ReaderId<E>) -> ReaderStats {
+        match &reader.handle {
+            None => ReaderStats::default(),
+            Some(handle) => self.channel.stats(handle),
+        }
+    }
+}
+
+impl<E: 'static + Send + Sync> Flush for Events<E> {
+    fn flush(&mut self) {
+        Events::flush(self);
+    }
+}
+
+/// Read position of a system in `Events<E>`, keep it in the system struct
+pub struct ReaderId<E> {
+    handle: Option<ReaderHandle>,
+    _phantom: PhantomData<fn() -> E>,
+}
+
+impl<E> Default for ReaderId<E> {
+    fn default() -> Self {
+        Self {
+            handle: None,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+/// Sends events, ordered before the systems reading the same events
+pub struct EventWriter<'r, E: 'static + Send + Sync> {
+    events: Write<'r, Events<E>>,
+}
+
+impl<E: 'static + Send + Sync> Deref for EventWriter<'_, E> {
+    type Target = Events<E>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.events
+    }
+}
+
+impl<E: 'static + Send + Sync> DerefMut for EventWriter<'_, E> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.events
+    }
+}
+
+impl<'r, E: 'static + Send + Sync> SystemData<'r> for EventWriter<'r, E> {
+    unsafe fn fetch(world: &'r World) -> Self {
+        world.add_flusher::<Events<E>>();
+        EventWriter {
+            events: Write::fetch(world),
+        }
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        Write::<Events<E>>::writes()
+    }
+}
+
+/// Reads events with a `ReaderId`, ordered after the systems sending the same events
+pub struct EventReader<'r, E: 'static + Send + Sync> {
+    events: RAW<'r, Events<E>>,
+}
+
+impl<E: 'static + Send + Sync> Deref for EventReader<'_, E> {
+    type Target = Events<E>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.events
+    }
+}
+
+impl<'r, E: 'static + Send + Sync> SystemData<'r> for EventReader<'r, E> {
+    unsafe fn fetch(world: &'r World) -> Self {
+        world.add_flusher::<Events<E>>();
+        EventReader {
+            events: RAW::fetch(world),
+        }
+    }
+
+    fn reads_after_write() -> Vec<ResourceId> {
+        RAW::<Events<E>>::reads_after_write()
+    }
+}
+
+/// Sends events concurrently with the other senders.
+/// The events are flushed by `World::flush_resources` at the end of the frame, so they are read in the next frame
+pub struct EventSender<'r, E> {
+    events: &'r Events<E>,
+}
+
+impl<E> EventSender<'_, E> {
+    pub fn send(&self, event: E) {
+        self.events.send_concurrent(event);
+    }
+}
+
+impl<'r, E: 'static + Send + Sync> SystemData<'r> for EventSender<'r, E> {
+    unsafe fn fetch(world: &'r World) -> Self {
+        world.add_flusher::<Events<E>>();
+        EventSender {
+            events: world.fetch(),
+        }
+    }
+
+    fn reads_before_write() -> Vec<ResourceId> {
+        vec![ResourceId::new::<Events<E>>()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::*;
+
+    struct Collision {
+        damage: usize,
+    }
+
+    static RECEIVED_DAMAGE: AtomicUsize = AtomicUsize::new(0);
+
+    #[system]
+    struct CollisionReader {
+        reader: ReaderId<Collision>,
+    }
+
+    impl<'r> System<'r> for CollisionReader {
+        type SystemData = EventReader<'r, Collision>;
+
+        fn run(&mut self, events: Self::SystemData) {
+            for collision in events.read(&mut self.reader) {
+                RECEIVED_DAMAGE.fetch_add(collision.damage, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[system]
+    struct CollisionWriter {}
+
+    impl<'r> System<'r> for CollisionWriter {
+        type SystemData = EventWriter<'r, Collision>;
+
+        fn run(&mut self, mut events: Self::SystemData) {
+            events.send(Collision { damage: 1 });
+            events.send_batch(vec![Collision { damage: 2 }, Collision { damage: 3 }]);
+        }
+    }
+
+    #[derive(Copy, Clone)]
+    struct Score(usize);
+
+    static RECEIVED_SCORE: AtomicUsize = AtomicUsize::new(0);
+
+    #[system]
+    struct ScoreSender0 {}
+
+    impl<'r> System<'r> for ScoreSender0 {
+        type SystemData = EventSender<'r, Score>;
+
+        fn run(&mut self, sender: Self::SystemData) {
+            sender.send(Score(1));
+        }
+    }
+
+    #[system]
+    struct ScoreSender1 {}
+
+    impl<'r> System<'r> for ScoreSender1 {
+        type SystemData = EventSender<'r, Score>;
+
+        fn run(&mut self, sender: Self::SystemData) {
+            sender.send(Score(10));
+        }
+    }
+
+    #[system]
+    struct ScoreReader {
+        reader: ReaderId<Score>,
+    }
+
+    impl<'r> System<'r> for ScoreReader {
+        type SystemData = EventReader<'r, Score>;
+
+        fn run(&mut self, events: Self::SystemData) {
+            for score in events.read(&mut self.reader) {
+                RECEIVED_SCORE.fetch_add(score.0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[test]
+    fn read_events() {
+        let mut events = Events::default();
+        let mut reader0 = ReaderId::default();
+        assert_eq!(events.read(&mut reader0).count(), 0);
+
+        events.send(0);
+        let mut reader1 = ReaderId::default();
+        assert_eq!(
+            events.read(&mut reader0).copied().collect::<Vec<_>>(),
+            vec![0]
+        );
+        assert_eq!(
+            events.read(&mut reader1).copied().collect::<Vec<_>>(),
+            vec![0]
+        );
+        events.send(1);
+        assert_eq!(
+            events.read(&mut reader1).copied().collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert_eq!(
+            events.read(&mut reader0).copied().collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert_eq!(events.read(&mut reader0).count(), 0);
+
+        let mut events = Events::with_capacity(1, OverflowPolicy::DropOldest);
+        let mut reader = ReaderId::default();
+        assert_eq!(events.read(&mut reader).count(), 0);
+        events.send_batch(vec![0, 1]);
+        assert_eq!(events.stats(&reader).missed, 1);
+        assert_eq!(
+            events.read(&mut reader).copied().collect::<Vec<_>>(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn unread_events_released_on_flush() {
+        let mut events = Events::default();
+        events.send_batch(0..100);
+        let mut early = ReaderId::default();
+        assert_eq!(events.read(&mut early).count(), 100);
+
+        let mut events = Events::default();
+        for frame in 0..10 {
+            events.send_batch(0..100);
+            events.send_concurrent(frame);
+            events.flush();
+        }
+        let mut late = ReaderId::default();
+        assert_eq!(events.read(&mut late).count(), 0);
+        events.send(0);
+        assert_eq!(events.read(&mut late).count(), 1);
+    }
+
+    #[test]
+    fn writer_before_reader() {
+        let mut world = World::default();
+        world.insert(Events::<Collision>::default);
+        let mut scheduler = Scheduler::new(&mut world);
+        scheduler.update(&mut world);
+        assert_eq!(RECEIVED_DAMAGE.load(Ordering::Relaxed), 6);
+        scheduler.update(&mut world);
+        assert_eq!(RECEIVED_DAMAGE.load(Ordering::Relaxed), 12);
+    }
+
+    #[test]
+    fn concurrent_senders() {
+        let mut world = World::default();
+        world.insert(Events::<Score>::default);
+        let mut scheduler = Scheduler::new(&mut world);
+        scheduler.update(&mut world);
+        assert_eq!(RECEIVED_SCORE.load(Ordering::Relaxed), 0);
+        scheduler.update(&mut world);
+        assert_eq!(RECEIVED_SCORE.load(Ordering::Relaxed), 11);
+        scheduler.update(&mut world);
+        assert_eq!(RECEIVED_SCORE.load(Ordering::Relaxed), 22);
+    }
+}
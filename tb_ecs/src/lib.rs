@@ -3,17 +3,28 @@
 
 pub use inventory;
 
+pub use bundle::*;
+pub use command::*;
 pub use component::*;
+pub use diff::*;
 pub use entity::*;
+pub use event::*;
 pub use join::*;
 pub use scheduler::*;
+pub use snapshot::*;
 pub use system::*;
 pub use tb_ecs_macro::*;
 pub use world::*;
 
+mod bundle;
+mod clone;
+mod command;
 mod component;
+mod diff;
 mod entity;
+mod event;
 mod join;
 mod scheduler;
+mod snapshot;
 mod system;
 mod world;
@@ -9,10 +9,12 @@ use rayon::prelude::*;
 use tb_core::algorithm::topological_sort::{Node, TopologicalGraph};
 use tb_core::event_channel::ReaderHandle;
 
+use crate::system::build_graph;
 use crate::{System, SystemData, SystemInfo, SystemRegistry, World};
 
 pub struct Scheduler {
     resources_change_event_reader: ReaderHandle,
+    local_systems: Vec<SystemInfo>,
     systems: Vec<RunnableCell>,
     dependants: Vec<DashSet<usize>>,
     dependencies_counter_cache: Vec<AtomicUsize>,
@@ -20,10 +22,18 @@ pub struct Scheduler {
 }
 
 impl Scheduler {
+    /// Run the systems of the global `SystemRegistry`
     pub fn new(world: &mut World) -> Self {
+        Self::with_systems(world, vec![])
+    }
+
+    /// Run `local_systems` along with the systems of the global `SystemRegistry`,
+    /// without registering them, a local system already in the registry is skipped
+    pub fn with_systems(world: &mut World, local_systems: Vec<SystemInfo>) -> Self {
         let channel = world.resource_change_events_mut();
         let resources_change_event_reader = channel.register();
         let mut scheduler = Self {
+            local_systems,
             systems: vec![],
             dependants: vec![],
             dependencies_counter_cache: vec![],
@@ -53,7 +63,10 @@ impl Scheduler {
             .into_par_iter()
             .for_each(|i| unsafe {
                 self.run_system_recursive(i, world);
-            })
+            });
+
+        world.maintain();
+        world.flush_resources();
     }
 
     unsafe fn run_system_recursive(&self, i: usize, world: &World) {
@@ -71,6 +84,28 @@ impl Scheduler {
         let mut sr = SystemRegistry::get_instance();
         let sr: &mut SystemRegistry = &mut sr;
         let systems = sr.systems();
+        if self.local_systems.is_empty() {
+            self.schedule(world, systems);
+            return;
+        }
+
+        let local_systems = std::mem::take(&mut self.local_systems);
+        {
+            let mut infos: Vec<&SystemInfo> = systems.par_iter().map(|(&info, _)| info).collect();
+            for local_system in &local_systems {
+                if infos
+                    .iter()
+                    .all(|info| info.system_type_id() != local_system.system_type_id())
+                {
+                    infos.push(local_system);
+                }
+            }
+            self.schedule(world, &build_graph(infos));
+        }
+        self.local_systems = local_systems;
+    }
+
+    fn schedule(&mut self, world: &World, systems: &TopologicalGraph<&SystemInfo>) {
         let infos: Vec<_> = systems
             .par_iter()
             .filter(|(&system_info, _node)| system_info.is_resource_matched(world))
@@ -0,0 +1,308 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize, Serializer};
+use tb_core::serde::serde_json::{Number, Value};
+
+pub use errors::{
+    Error as SnapshotError, ErrorKind as SnapshotErrorKind, Result as SnapshotResult,
+};
+
+use crate::{ComponentRegistry, Entities, Entity, LocalToWorldLink, World};
+
+mod errors {
+    pub use tb_core::error::*;
+
+    error_chain! {
+        links {
+            Reflect(crate::ReflectError, crate::ReflectErrorKind);
+        }
+
+        errors {
+            UnknownEntity(entity: crate::Entity) {
+                description("Component belongs to an entity which is not in the snapshot"),
+                display("Component belongs to an entity which is not in the snapshot. entity: {:?}", entity),
+            }
+            UnsupportedVersion(version: u32) {
+                description("Unsupported snapshot version"),
+                display("Unsupported snapshot version. version: {}, supported: {}", version, super::WorldSnapshot::VERSION),
+            }
+        }
+    }
+}
+
+/// Serialized entities and components of a world, keyed by the registered component names
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    version: u32,
+    entities: Vec<Entity>,
+    #[serde(serialize_with = "serialize_components")]
+    components: BTreeMap<String, Vec<(Entity, Value)>>,
+}
+
+/// Binary formats get the numbers of the components as native numbers,
+/// instead of the structs serialized for them by `arbitrary_precision`
+fn serialize_components<S: Serializer>(
+    components: &BTreeMap<String, Vec<(Entity, Value)>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        return components.serialize(serializer);
+    }
+    serializer.collect_map(components.iter().map(|(name, entries)| {
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|(entity, value)| (entity, CompactValue(value)))
+            .collect();
+        (name, entries)
+    }))
+}
+
+struct CompactValue<'a>(&'a Value);
+
+impl Serialize for CompactValue<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::Number(number) => serialize_number(number, serializer),
+            Value::Array(array) => serializer.collect_seq(array.iter().map(CompactValue)),
+            Value::Object(object) => {
+                serializer.collect_map(object.iter().map(|(key, value)| (key, CompactValue(value))))
+            }
+            value => value.serialize(serializer),
+        }
+    }
+}
+
+/// As a u64, i64 or f64 if it's read back the same, otherwise as the string written by `arbitrary_precision`
+fn serialize_number<S: Serializer>(number: &Number, serializer: S) -> Result<S::Ok, S::Error> {
+    if let Some(value) = number
+        .as_u64()
+        .filter(|value| Number::from(*value) == *number)
+    {
+        serializer.serialize_u64(value)
+    } else if let Some(value) = number
+        .as_i64()
+        .filter(|value| Number::from(*value) == *number)
+    {
+        serializer.serialize_i64(value)
+    } else if let Some(value) = number
+        .as_f64()
+        .filter(|value| Number::from_f64(*value).as_ref() == Some(number))
+    {
+        serializer.serialize_f64(value)
+    } else {
+        serializer.serialize_str(&number.to_string())
+    }
+}
+
+impl WorldSnapshot {
+    pub const VERSION: u32 = 1;
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn components(&self) -> &BTreeMap<String, Vec<(Entity, Value)>> {
+        &self.components
+    }
+}
+
+impl World {
+    /// Serialize all live entities and their registered components
+    pub fn snapshot(&self) -> SnapshotResult<WorldSnapshot> {
+        let entities = match unsafe { self.try_fetch::<Entities>() } {
+            Ok(entities) => entities.iter().collect(),
+            Err(_) => vec![],
+        };
+
+        let mut components = BTreeMap::new();
+        for info in ComponentRegistry::infos() {
+            let entries = unsafe { info.to_json_entries(self) }?;
+            if entries.is_empty() {
+                continue;
+            }
+            components.insert(info.name().to_owned(), entries);
+        }
+
+        Ok(WorldSnapshot {
+            version: WorldSnapshot::VERSION,
+            entities,
+            components,
+        })
+    }
+
+    /// Kill all live entities and rebuild them from `snapshot`.
+    /// The world is left untouched if a component name is not registered or belongs to an entity which is not in `snapshot`.
+    /// References to entities which are not in `snapshot` stay dangling.
+    /// Returns the link from the entities in `snapshot` to the new ones
+    pub fn restore(&mut self, snapshot: &WorldSnapshot) -> SnapshotResult<LocalToWorldLink> {
+        if snapshot.version != WorldSnapshot::VERSION {
+            return Err(SnapshotErrorKind::UnsupportedVersion(snapshot.version).into());
+        }
+
+        let known: HashSet<Entity> = snapshot.entities.iter().copied().collect();
+        let mut infos = Vec::with_capacity(snapshot.components.len());
+        for (name, entries) in &snapshot.components {
+            if let Some((local, _)) = entries.iter().find(|(local, _)| !known.contains(local)) {
+                return Err(SnapshotErrorKind::UnknownEntity(*local).into());
+            }
+            infos.push((World::component_info(name)?, entries));
+        }
+
+        let alive: Vec<Entity> = self.insert(Entities::default).iter().collect();
+        for entity in alive {
+            self.kill(entity);
+        }
+
+        let mut link = LocalToWorldLink::default();
+        let entities = unsafe { self.fetch::<Entities>() };
+        for &local in &snapshot.entities {
+            link.build_link(local, entities);
+        }
+
+        for (info, entries) in infos {
+            for (local, value) in entries {
+                let entity = link.get(*local).unwrap();
+                info.insert_linked_json(self, entity, value.clone(), &link)?;
+            }
+        }
+
+        Ok(link)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tb_core::serde::serde_json;
+
+    use crate::*;
+
+    #[component]
+    struct Health {
+        value: i32,
+    }
+
+    #[component]
+    struct Target {
+        target: Entity,
+        others: Vec<Entity>,
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let mut world = World::default();
+        let entity0 = world.create_entity().with(Health { value: 10 }).create();
+        let entity1 = world
+            .create_entity()
+            .with(Health { value: 20 })
+            .with(Target {
+                target: entity0,
+                others: vec![entity0],
+            })
+            .create();
+
+        let snapshot = world.snapshot().unwrap();
+        assert_eq!(snapshot.entities().len(), 2);
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let snapshot: WorldSnapshot = serde_json::from_str(&json).unwrap();
+
+        unsafe { world.fetch_components_mut::<Health>() }
+            .get_mut(entity0)
+            .unwrap()
+            .value = 0;
+        world.create_entity().with(Health { value: 30 }).create();
+
+        let link = world.restore(&snapshot).unwrap();
+        let entities = unsafe { world.fetch::<Entities>() };
+        assert_eq!(entities.iter().count(), 2);
+        let new_entity0 = link.get(entity0).unwrap();
+        let new_entity1 = link.get(entity1).unwrap();
+        assert!(entities.is_alive(new_entity0));
+        assert!(entities.is_alive(new_entity1));
+
+        let health = unsafe { world.fetch_components::<Health>() };
+        assert_eq!(health.get(new_entity0).unwrap().value, 10);
+        assert_eq!(health.get(new_entity1).unwrap().value, 20);
+        let target = unsafe { world.fetch_components::<Target>() };
+        let target = target.get(new_entity1).unwrap();
+        assert_eq!(target.target, new_entity0);
+        assert_eq!(target.others, vec![new_entity0]);
+
+        let healths = unsafe { RAWComponents::<Health>::fetch(&world) };
+        assert_eq!((&healths).join().count(), 2);
+    }
+
+    #[test]
+    fn restore_dangling_reference() {
+        let mut world = World::default();
+        let entity0 = world.create_entity().create();
+        let entity1 = world
+            .create_entity()
+            .with(Target {
+                target: entity0,
+                others: vec![entity0, entity0],
+            })
+            .create();
+        world.kill(entity0);
+        let snapshot = world.snapshot().unwrap();
+        assert_eq!(snapshot.entities(), &[entity1]);
+
+        let mut other = World::default();
+        other.create_entity().create();
+        other.create_entity().create();
+        let link = other.restore(&snapshot).unwrap();
+        let entities = unsafe { other.fetch::<Entities>() };
+        assert_eq!(entities.iter().count(), 1);
+        assert!(link.get(entity0).is_none());
+
+        let target = unsafe { other.fetch_components::<Target>() }
+            .get(link.get(entity1).unwrap())
+            .unwrap()
+            .clone();
+        assert!(!entities.is_alive(target.target));
+        assert!(target.others.iter().all(|e| !entities.is_alive(*e)));
+        other.create_entity().create();
+        assert!(!unsafe { other.fetch::<Entities>() }.is_alive(target.target));
+    }
+
+    #[test]
+    fn restore_unknown_entity() {
+        let mut world = World::default();
+        let entity = world.create_entity().with(Health { value: 10 }).create();
+        let mut snapshot = world.snapshot().unwrap();
+        snapshot.entities.clear();
+        let mut other = World::default();
+        let alive = other.create_entity().with(Health { value: 5 }).create();
+        match other.restore(&snapshot) {
+            Err(SnapshotError(SnapshotErrorKind::UnknownEntity(e), _)) => assert_eq!(e, entity),
+            _ => panic!("restoring a component of an unknown entity should fail"),
+        }
+        assert!(unsafe { other.fetch::<Entities>() }.is_alive(alive));
+        assert_eq!(
+            unsafe { other.fetch_components::<Health>() }
+                .get(alive)
+                .unwrap()
+                .value,
+            5
+        );
+    }
+
+    #[test]
+    fn restore_unregistered_component() {
+        let mut world = World::default();
+        let entity = world.create_entity().with(Health { value: 10 }).create();
+        let mut snapshot = world.snapshot().unwrap();
+        snapshot.components.insert(
+            "NotRegistered".into(),
+            vec![(entity, serde_json::json!({}))],
+        );
+
+        let mut other = World::default();
+        let alive = other.create_entity().create();
+        assert!(other.restore(&snapshot).is_err());
+        assert!(unsafe { other.fetch::<Entities>() }.is_alive(alive));
+    }
+}
@@ -1,5 +1,6 @@
 use std::any::TypeId;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
 use std::hash::{Hash, Hasher};
 use std::lazy::SyncLazy;
 use std::sync::{Mutex, MutexGuard};
@@ -15,7 +16,6 @@ use crate::{System, SystemData, World};
 
 pub struct SystemRegistry {
     systems: HashMap<TypeId, &'static SystemInfo>,
-    resources_info: HashMap<ResourceId, ResourceInfo>,
     system_topological_graph:
         tb_core::algorithm::topological_sort::TopologicalGraph<&'static SystemInfo>,
     system_changed_events: EventChannel<()>,
@@ -25,11 +25,10 @@ pub struct SystemRegistry {
 impl SystemRegistry {
     pub fn get_instance() -> MutexGuard<'static, SystemRegistry> {
         static SYSTEM_REGISTRY: SyncLazy<Mutex<SystemRegistry>> = SyncLazy::new(|| {
-            let mut system_changed_events = EventChannel::default();
+            let system_changed_events = EventChannel::default();
             let system_changed_reader = system_changed_events.register();
             let mut registry = SystemRegistry {
                 systems: Default::default(),
-                resources_info: Default::default(),
                 system_topological_graph: Default::default(),
                 system_changed_events,
                 system_changed_reader,
@@ -75,78 +74,85 @@ impl SystemRegistry {
     }
 
     fn refresh(&mut self) {
-        let resources_info = &mut self.resources_info;
-        resources_info.clear();
-        self.systems.values().for_each(|system_info| {
-            system_info
-                .reads_before_write
-                .iter()
-                .for_each(|resource_id| {
-                    resources_info
-                        .entry(*resource_id)
-                        .or_insert_with(ResourceInfo::default)
-                        .read_before_write_systems
-                        .insert(system_info);
-                });
-            system_info.writes.iter().for_each(|resource_id| {
+        self.system_topological_graph = build_graph(self.systems.values().copied().collect());
+    }
+}
+
+/// Order the systems by the resources they access
+pub(crate) fn build_graph(mut system_infos: Vec<&SystemInfo>) -> TopologicalGraph<&SystemInfo> {
+    // Sorted by name so that the graph and the schedule don't depend on the hash order
+    system_infos.sort_by_key(|system_info| system_info.name);
+
+    let mut resources_info: HashMap<ResourceId, ResourceInfo> = HashMap::new();
+    system_infos.iter().for_each(|&system_info| {
+        system_info
+            .reads_before_write
+            .iter()
+            .for_each(|resource_id| {
                 resources_info
                     .entry(*resource_id)
                     .or_insert_with(ResourceInfo::default)
-                    .write_systems
-                    .insert(system_info);
+                    .read_before_write_systems
+                    .push(system_info);
             });
-            system_info
-                .reads_after_write
-                .iter()
-                .for_each(|resource_id| {
-                    resources_info
-                        .entry(*resource_id)
-                        .or_insert_with(ResourceInfo::default)
-                        .read_after_write_systems
-                        .insert(system_info);
-                });
+        system_info.writes.iter().for_each(|resource_id| {
+            resources_info
+                .entry(*resource_id)
+                .or_insert_with(ResourceInfo::default)
+                .write_systems
+                .push(system_info);
         });
-
-        let graph = &mut self.system_topological_graph;
-        graph.clear();
-        self.systems.values().for_each(|system_info| {
-            graph.add_item(system_info);
-            system_info.writes.iter().for_each(|write_resource| {
-                let write_resource_info = resources_info.get(write_resource).unwrap();
-                write_resource_info
-                    .read_before_write_systems
-                    .iter()
-                    .for_each(|read_before_write_system| {
-                        graph.add_dependency(system_info, read_before_write_system);
-                    });
-                write_resource_info
+        system_info
+            .reads_after_write
+            .iter()
+            .for_each(|resource_id| {
+                resources_info
+                    .entry(*resource_id)
+                    .or_insert_with(ResourceInfo::default)
                     .read_after_write_systems
-                    .iter()
-                    .for_each(|read_after_write_system| {
-                        graph.add_dependency(read_after_write_system, system_info);
-                    });
+                    .push(system_info);
             });
+    });
+
+    let mut graph = TopologicalGraph::default();
+    system_infos.iter().for_each(|&system_info| {
+        graph.add_item(system_info);
+        system_info.writes.iter().for_each(|write_resource| {
+            let write_resource_info = resources_info.get(write_resource).unwrap();
+            write_resource_info
+                .read_before_write_systems
+                .iter()
+                .for_each(|read_before_write_system| {
+                    graph.add_dependency(system_info, read_before_write_system);
+                });
+            write_resource_info
+                .read_after_write_systems
+                .iter()
+                .for_each(|read_after_write_system| {
+                    graph.add_dependency(read_after_write_system, system_info);
+                });
         });
+    });
 
-        self.systems.values().for_each(|system_info| {
-            system_info.writes.iter().for_each(|write_resource| {
-                let write_resource_info = resources_info.get(write_resource).unwrap();
-                write_resource_info
-                    .write_systems
-                    .iter()
-                    .for_each(|write_system| {
-                        graph.add_dependency_if_non_inverse(write_system, system_info);
-                    })
-            });
+    system_infos.iter().for_each(|&system_info| {
+        system_info.writes.iter().for_each(|write_resource| {
+            let write_resource_info = resources_info.get(write_resource).unwrap();
+            write_resource_info
+                .write_systems
+                .iter()
+                .for_each(|write_system| {
+                    graph.add_dependency_if_non_inverse(write_system, system_info);
+                })
         });
-    }
+    });
+    graph
 }
 
 #[derive(Default)]
-pub struct ResourceInfo {
-    read_before_write_systems: HashSet<&'static SystemInfo>,
-    write_systems: HashSet<&'static SystemInfo>,
-    read_after_write_systems: HashSet<&'static SystemInfo>,
+pub struct ResourceInfo<'s> {
+    read_before_write_systems: Vec<&'s SystemInfo>,
+    write_systems: Vec<&'s SystemInfo>,
+    read_after_write_systems: Vec<&'s SystemInfo>,
 }
 
 pub struct SystemInfo {
@@ -201,6 +207,12 @@ impl SystemInfo {
     }
 }
 
+impl Debug for SystemInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
 impl PartialEq for &SystemInfo {
     fn eq(&self, other: &Self) -> bool {
         (*self as *const SystemInfo).eq(&(*other as *const SystemInfo))
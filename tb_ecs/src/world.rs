@@ -2,10 +2,13 @@ use std::any::TypeId;
 use std::cell::UnsafeCell;
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
 
 use errors::*;
 use tb_core::event_channel::EventChannel;
 
+use crate::Commands;
+
 mod errors {
     pub use tb_core::error::*;
 
@@ -40,11 +43,14 @@ impl ResourceCell {
 unsafe impl Sync for ResourceCell {}
 
 type Resources = HashMap<ResourceId, ResourceCell>;
+type Flushers = HashMap<ResourceId, fn(&mut World)>;
 
 #[derive(Default)]
 pub struct World {
     resources: Resources,
     resource_change_events: EventChannel<ResourceChangeEvent>,
+    deferred_commands: Mutex<Commands>,
+    flushers: Mutex<Flushers>,
 }
 
 impl World {
@@ -126,6 +132,45 @@ impl World {
     pub fn contains_id(&self, id: &ResourceId) -> bool {
         self.resources.contains_key(&id)
     }
+
+    /// Queue commands which will be applied on next `maintain`
+    pub fn defer(&self, commands: &mut Commands) {
+        if !commands.is_empty() {
+            self.deferred_commands.lock().unwrap().append(commands);
+        }
+    }
+
+    /// Flush the resource `R` on every `flush_resources` from now on
+    pub fn add_flusher<R: Flush>(&self) {
+        self.flushers
+            .lock()
+            .unwrap()
+            .entry(ResourceId::new::<R>())
+            .or_insert(|world| {
+                if let Ok(resource) = unsafe { world.try_fetch_mut::<R>() } {
+                    resource.flush();
+                }
+            });
+    }
+
+    /// Flush the resources registered by `add_flusher`, called by `Scheduler::update` once per frame
+    pub fn flush_resources(&mut self) {
+        let flushers: Vec<_> = self.flushers.get_mut().unwrap().values().copied().collect();
+        for flush in flushers {
+            flush(self);
+        }
+    }
+
+    /// Apply the commands queued by `defer`, such as the ones of component hooks
+    pub fn maintain(&mut self) {
+        loop {
+            let mut commands = std::mem::take(self.deferred_commands.get_mut().unwrap());
+            if commands.is_empty() {
+                break;
+            }
+            commands.apply(self);
+        }
+    }
 }
 
 #[derive(Eq, PartialEq, Hash, Copy, Clone)]
@@ -145,6 +190,11 @@ pub trait Resource: 'static + Sync {}
 
 impl<R: 'static + Sync> Resource for R {}
 
+/// A resource with work buffered through shared references, see `World::add_flusher`
+pub trait Flush: Resource {
+    fn flush(&mut self);
+}
+
 pub struct ResourceChangeEvent {}
 
 impl ResourceChangeEvent {
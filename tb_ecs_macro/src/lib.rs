@@ -3,6 +3,8 @@
 use proc_macro::TokenStream;
 
 use quote::*;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
 use syn::*;
 
 #[proc_macro_attribute]
@@ -19,14 +21,93 @@ pub fn system(_attr: TokenStream, item: TokenStream) -> TokenStream {
     output.into()
 }
 
+#[derive(Default)]
+struct ComponentArgs {
+    name: Option<LitStr>,
+    on_add: Option<Path>,
+    on_remove: Option<Path>,
+    on_replace: Option<Path>,
+}
+
+enum ComponentArgValue {
+    Name(LitStr),
+    Hook(Path),
+}
+
+struct ComponentArg {
+    name: Ident,
+    _eq: Token![=],
+    value: ComponentArgValue,
+}
+
+impl Parse for ComponentArg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let name: Ident = input.parse()?;
+        let _eq = input.parse()?;
+        let value = if name == "name" {
+            ComponentArgValue::Name(input.parse()?)
+        } else {
+            ComponentArgValue::Hook(input.parse()?)
+        };
+        Ok(Self { name, _eq, value })
+    }
+}
+
+impl Parse for ComponentArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut args = ComponentArgs::default();
+        for arg in Punctuated::<ComponentArg, Token![,]>::parse_terminated(input)? {
+            let hook = match arg.value {
+                ComponentArgValue::Name(name) => {
+                    if args.name.replace(name).is_some() {
+                        return Err(Error::new(arg.name.span(), "duplicated component name"));
+                    }
+                    continue;
+                }
+                ComponentArgValue::Hook(hook) => hook,
+            };
+            let slot = match arg.name.to_string().as_str() {
+                "on_add" => &mut args.on_add,
+                "on_remove" => &mut args.on_remove,
+                "on_replace" => &mut args.on_replace,
+                _ => {
+                    return Err(Error::new(
+                        arg.name.span(),
+                        "expected `name`, `on_add`, `on_remove` or `on_replace`",
+                    ));
+                }
+            };
+            if slot.replace(hook).is_some() {
+                return Err(Error::new(arg.name.span(), "duplicated component hook"));
+            }
+        }
+        Ok(args)
+    }
+}
+
+fn component_hook(name: &str, hook: &Option<Path>) -> proc_macro2::TokenStream {
+    match hook {
+        None => quote! {},
+        Some(hook) => {
+            let name = format_ident!("{}", name);
+            quote! {
+                fn #name(&self, entity: Entity, commands: &mut Commands) {
+                    #hook(self, entity, commands)
+                }
+            }
+        }
+    }
+}
+
 #[proc_macro_attribute]
-pub fn component(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn component(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as ComponentArgs);
     let component_struct = parse_macro_input!(item as ItemStruct);
     let fields: Vec<&Field> = component_struct
         .fields
         .iter()
         .filter(|field| {
-            let ty = field.ty.to_token_stream().to_string();
+            let ty = field.ty.to_token_stream().to_string().replace(' ', "");
             ty == "Entity" || ty == "Vec<Entity>"
         })
         .collect();
@@ -82,14 +163,86 @@ pub fn component(_attr: TokenStream, item: TokenStream) -> TokenStream {
         }
     };
 
+    let impl_reflect = {
+        let name = match &args.name {
+            None => quote! {stringify!(#component_name)},
+            Some(name) => quote! {#name},
+        };
+        let mut field_names = vec![];
+        let mut field_members = vec![];
+        let mut field_types = vec![];
+        for (i, field) in component_struct.fields.iter().enumerate() {
+            match &field.ident {
+                None => {
+                    let index = Index::from(i);
+                    field_names.push(i.to_string());
+                    field_members.push(quote! {#index});
+                }
+                Some(ident) => {
+                    field_names.push(ident.to_string());
+                    field_members.push(quote! {#ident});
+                }
+            }
+            field_types.push(&field.ty);
+        }
+        quote! {
+            impl Reflect for #component_name {
+                fn type_name() -> &'static str {
+                    #name
+                }
+
+                fn fields() -> Vec<FieldInfo> {
+                    vec![#(FieldInfo::new::<#field_types>(#field_names)),*]
+                }
+
+                fn get_field(&self, name: &str) -> ReflectResult<serde_json::Value> {
+                    match name {
+                        #(#field_names => FieldInfo::get(&self.#field_members),)*
+                        _ => Err(FieldInfo::not_found::<Self>(name)),
+                    }
+                }
+
+                #[allow(unused_variables)]
+                fn set_field(&mut self, name: &str, value: serde_json::Value) -> ReflectResult<()> {
+                    match name {
+                        #(#field_names => FieldInfo::set(&mut self.#field_members, value),)*
+                        _ => Err(FieldInfo::not_found::<Self>(name)),
+                    }
+                }
+            }
+        }
+    };
+
+    let hooks = vec![
+        component_hook("on_add", &args.on_add),
+        component_hook("on_remove", &args.on_remove),
+        component_hook("on_replace", &args.on_replace),
+    ];
+
+    let for_each_entity_ref = if fields.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            fn for_each_entity_ref(&mut self, mut action: &mut dyn FnMut(&mut Entity)) {
+                EntityRef::for_each(&mut self.get_entity_ref(), &mut action)
+            }
+        }
+    };
+
     let output = quote! {
         #[derive(Clone, Deserialize, Serialize)]
         #component_struct
 
-        impl Component for #component_name {}
+        impl Component for #component_name {
+            #(#hooks)*
+
+            #for_each_entity_ref
+        }
 
         #impl_component_with_entity_ref
 
+        #impl_reflect
+
         inventory::submit! {
             ComponentInfo::new::<#component_name>()
         }
@@ -0,0 +1,21 @@
+/// Resource to quit the app after the current frame, write it from any system
+#[derive(Default)]
+pub struct AppExit {
+    code: Option<i32>,
+}
+
+impl AppExit {
+    /// Quit with `code` as the exit status, the first request wins
+    pub fn request(&mut self, code: i32) {
+        self.code.get_or_insert(code);
+    }
+
+    pub fn success(&mut self) {
+        self.request(0);
+    }
+
+    /// The exit status if an exit is requested
+    pub fn code(&self) -> Option<i32> {
+        self.code
+    }
+}
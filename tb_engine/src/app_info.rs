@@ -1,15 +1,24 @@
-use std::lazy::SyncLazy;
+use std::lazy::SyncOnceCell;
 use std::path::PathBuf;
 
 use errors::*;
+pub use errors::{Error as AppInfoError, ErrorKind as AppInfoErrorKind, Result as AppInfoResult};
 use tb_core::path_util;
 
+use crate::settings::{Settings, SettingsLayer};
+
 mod errors {
-    use tb_core::error::*;
+    pub use tb_core::error::*;
 
-    error_chain! {}
+    error_chain! {
+        links {
+            Settings(crate::settings::SettingsError, crate::settings::SettingsErrorKind);
+        }
+    }
 }
 
+static INSTANCE: SyncOnceCell<AppInfo> = SyncOnceCell::new();
+
 pub enum LaunchMethod {
     Project { project_dir: PathBuf },
     Archive,
@@ -21,52 +30,90 @@ pub struct AppInfo {
     pub engine_assets_dir: PathBuf,
     pub project_root_dir: PathBuf,
     pub project_assets_dir: PathBuf,
+    /// Archive packed from the project assets, found next to the executable when launched as `Archive`
+    pub project_archive: Option<PathBuf>,
+    pub settings: Settings,
 }
 
 impl AppInfo {
+    /// Set by `init`, or built from the command line and the environment variables at the first call.
+    /// The arguments which fail to parse are ignored with a warning, so strict parsing is up to the binary
     pub fn get() -> &'static Self {
-        static INSTANCE: SyncLazy<AppInfo> = SyncLazy::new(|| {
-            let mut method = LaunchMethod::Archive;
-            let mut args = std::env::args();
-            args.next();
-            while let Some(arg) = args.next() {
-                match arg.as_str() {
-                    "--project" | "-p" => {
-                        let project = args.next().unwrap();
-                        method = LaunchMethod::Project {
-                            project_dir: PathBuf::from(project),
-                        }
-                    }
-                    arg => {
-                        eprintln!("unknown argument: {}", arg);
-                    }
-                }
+        INSTANCE.get_or_init(|| {
+            let (cli, errors) = SettingsLayer::from_args_lenient(std::env::args().skip(1));
+            for e in errors {
+                log_warn!("Ignored command line argument: {}", e);
             }
+            AppInfo::build(cli, |key| std::env::var(key).ok())
+                .unwrap_or_else(|e| panic!("Failed to build AppInfo: {}", e.display_chain()))
+        })
+    }
 
-            let project_root_dir = match &method {
-                LaunchMethod::Project { project_dir } => project_dir.clone(),
-                LaunchMethod::Archive => std::env::current_dir()
-                    .chain_err(|| "Failed to get current_dir")
-                    .unwrap(),
-            };
-
-            let engine_root_dir = path_util::exe_dir();
-            AppInfo {
-                method,
-                engine_assets_dir: engine_root_dir.join(AppInfo::assets_dir_name()),
-                engine_root_dir,
-                project_assets_dir: project_root_dir.join(AppInfo::assets_dir_name()),
-                project_root_dir,
+    /// Set the instance returned by `get`, gives `app_info` back if it is already set
+    pub fn init(app_info: AppInfo) -> std::result::Result<(), AppInfo> {
+        INSTANCE.set(app_info)
+    }
+
+    /// Merge the settings of `args`, the environment variables read by `env` and `toybox.toml` of the project.
+    /// Fails on any invalid argument, including `--help`
+    pub fn new(
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        AppInfo::build(SettingsLayer::from_args(args)?, env)
+    }
+
+    fn build(cli: SettingsLayer, env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let env = SettingsLayer::from_env(env)?;
+        let method = match cli.project.clone().or_else(|| env.project.clone()) {
+            None => LaunchMethod::Archive,
+            Some(project_dir) => LaunchMethod::Project { project_dir },
+        };
+
+        let project_root_dir = match &method {
+            LaunchMethod::Project { project_dir } => project_dir.clone(),
+            LaunchMethod::Archive => {
+                std::env::current_dir().chain_err(|| "Failed to get current_dir")?
             }
-        });
+        };
+        let file = SettingsLayer::from_file(project_root_dir.join(Settings::CONFIG_FILE_NAME))?
+            .unwrap_or_default();
+        let default_name = project_root_dir
+            .file_name()
+            .map_or("toybox".into(), |name| name.to_string_lossy());
+        let settings = Settings::new(file.merge(env).merge(cli), default_name);
+        settings.validate()?;
 
-        &INSTANCE
+        let engine_root_dir = path_util::exe_dir();
+        let project_archive = match &method {
+            LaunchMethod::Project { .. } => None,
+            LaunchMethod::Archive => Some(engine_root_dir.join(AppInfo::archive_file_name()))
+                .filter(|archive| archive.is_file()),
+        };
+        Ok(AppInfo {
+            method,
+            engine_assets_dir: engine_root_dir.join(AppInfo::assets_dir_name()),
+            engine_root_dir,
+            project_assets_dir: project_root_dir.join(&settings.asset_dirs[0]),
+            project_root_dir,
+            project_archive,
+            settings,
+        })
     }
 
     pub fn assets_dir_name() -> &'static str {
         "assets"
     }
 
+    pub fn archive_file_name() -> &'static str {
+        "assets.tbpack"
+    }
+
+    /// Dir in the project root for the generated files, such as the imported assets
+    pub fn cache_dir_name() -> &'static str {
+        ".cache"
+    }
+
     pub fn extern_entity_dir_name() -> &'static str {
         "__EXTERN_ENTITY__"
     }
@@ -0,0 +1,308 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt::{Display, Formatter};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::asset::errors::*;
+use crate::vfs::Vfs;
+
+/// Persistent id of an asset, stored in the `.meta` file next to it
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct AssetGuid(u128);
+
+impl AssetGuid {
+    /// A random guid
+    pub fn generate() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let seed = (
+            SystemTime::now(),
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+        );
+        let state = RandomState::new();
+        let mut high = state.build_hasher();
+        seed.hash(&mut high);
+        let high = high.finish();
+        let mut low = state.build_hasher();
+        (seed, high).hash(&mut low);
+        Self((high as u128) << 64 | low.finish() as u128)
+    }
+}
+
+impl Display for AssetGuid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl FromStr for AssetGuid {
+    type Err = Error;
+
+    /// 32 hex digits
+    fn from_str(s: &str) -> Result<Self> {
+        if s.len() != 32 {
+            bail!("Invalid asset guid: {}", s);
+        }
+        u128::from_str_radix(s, 16)
+            .map(AssetGuid)
+            .chain_err(|| format!("Invalid asset guid: {}", s))
+    }
+}
+
+impl Serialize for AssetGuid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for AssetGuid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let guid = String::deserialize(deserializer)?;
+        guid.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Content of the `.meta` sidecar file, in toml
+#[derive(Deserialize, Serialize)]
+pub struct AssetMeta {
+    pub guid: AssetGuid,
+}
+
+impl AssetMeta {
+    pub const EXTENSION: &'static str = "meta";
+
+    /// `<path>.meta`
+    pub fn path_of(asset_path: &Path) -> PathBuf {
+        let mut path = OsString::from(asset_path.as_os_str());
+        path.push(".");
+        path.push(Self::EXTENSION);
+        path.into()
+    }
+
+    pub fn read(vfs: &Vfs, asset_path: &Path) -> Result<AssetMeta> {
+        let path = Self::path_of(asset_path);
+        let data = vfs
+            .read(&path)
+            .chain_err(|| format!("Failed to read meta file. path: {:?}", path))?;
+        let text = std::str::from_utf8(&data).chain_err(|| "Meta file is not utf8")?;
+        toml::from_str(text).chain_err(|| format!("Failed to parse meta file. path: {:?}", path))
+    }
+
+    pub fn write(&self, vfs: &Vfs, asset_path: &Path) -> Result<()> {
+        let path = Self::path_of(asset_path);
+        let text = toml::to_string(self).chain_err(|| "Failed to serialize meta file")?;
+        vfs.write(&path, text.as_bytes())
+            .chain_err(|| format!("Failed to write meta file. path: {:?}", path))
+    }
+}
+
+/// Guids of the assets in the scanned dirs of a `Vfs`, by the virtual paths.
+/// An asset without `.meta` gets a new guid written to its `.meta`,
+/// or kept in memory only if the asset is in a read-only file system or the write fails
+#[derive(Default)]
+pub struct AssetDatabase {
+    guid_to_path: HashMap<AssetGuid, PathBuf>,
+    path_to_guid: HashMap<PathBuf, AssetGuid>,
+}
+
+impl AssetDatabase {
+    pub fn path_of(&self, guid: AssetGuid) -> Option<&Path> {
+        self.guid_to_path.get(&guid).map(|path| path.as_path())
+    }
+
+    pub fn guid_of(&self, path: &Path) -> Option<AssetGuid> {
+        self.path_to_guid.get(path).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.guid_to_path.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.guid_to_path.is_empty()
+    }
+
+    /// Clear and scan `dirs` recursively, the dirs not existing are skipped
+    pub fn rebuild(&mut self, vfs: &Vfs, dirs: &[PathBuf]) -> Result<()> {
+        self.guid_to_path.clear();
+        self.path_to_guid.clear();
+        for dir in dirs {
+            if vfs.exists(dir) {
+                self.scan(vfs, dir)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The guid of the asset at `path`, read from or written to its `.meta`.
+    /// The guid of an asset moved from a path which no longer exists is kept.
+    /// The `.meta` is written next to the asset only, never to another file system mounted at the same point
+    pub fn register(&mut self, vfs: &Vfs, path: &Path) -> Result<AssetGuid> {
+        if let Some(guid) = self.guid_of(path) {
+            return Ok(guid);
+        }
+        let guid = match AssetMeta::read(vfs, path) {
+            Ok(meta) => match self.guid_to_path.get(&meta.guid) {
+                None => meta.guid,
+                Some(other) if vfs.exists(other) => {
+                    log_warn!(
+                        "Duplicated asset guid, a new one is generated. guid: {}, path: {:?}, other: {:?}",
+                        meta.guid, path, other
+                    );
+                    self.write_new_meta(vfs, path)
+                }
+                // moved since registered
+                Some(other) => {
+                    self.path_to_guid.remove(other);
+                    meta.guid
+                }
+            },
+            Err(_) => self.write_new_meta(vfs, path),
+        };
+        self.guid_to_path.insert(guid, path.to_owned());
+        self.path_to_guid.insert(path.to_owned(), guid);
+        Ok(guid)
+    }
+
+    fn write_new_meta(&self, vfs: &Vfs, path: &Path) -> AssetGuid {
+        let meta = AssetMeta {
+            guid: AssetGuid::generate(),
+        };
+        if vfs.is_read_only(path) {
+            log_warn!(
+                "Asset without meta in a read-only file system, its guid is not persistent. path: {:?}",
+                path
+            );
+        } else if let Err(e) = meta.write(vfs, path) {
+            log_error!("{}", e.display_chain());
+        }
+        meta.guid
+    }
+
+    fn scan(&mut self, vfs: &Vfs, dir: &Path) -> Result<()> {
+        let entries = vfs
+            .read_dir(dir)
+            .chain_err(|| format!("Failed to read asset dir. path: {:?}", dir))?;
+        for entry in entries {
+            let name = entry.file_name().unwrap_or_default().to_string_lossy();
+            let is_meta = entry
+                .extension()
+                .map_or(false, |ext| ext == AssetMeta::EXTENSION);
+            if name.starts_with('.') || is_meta {
+                continue;
+            }
+            // only files fail to be read as dirs
+            match vfs.read_dir(&entry) {
+                Ok(_) => self.scan(vfs, &entry)?,
+                Err(_) => {
+                    self.register(vfs, &entry)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    use crate::asset::database::{AssetDatabase, AssetGuid, AssetMeta};
+    use crate::vfs::{ArchiveFileSystem, FileSystem, MemoryFileSystem, Packer, Vfs};
+
+    #[test]
+    fn guid() {
+        let guid = AssetGuid::generate();
+        assert_ne!(guid, AssetGuid::generate());
+        assert_eq!(guid.to_string().parse::<AssetGuid>().unwrap(), guid);
+        assert!("1234".parse::<AssetGuid>().is_err());
+    }
+
+    #[test]
+    fn rebuild_after_move() {
+        let vfs = Vfs::default();
+        let fs = Arc::new(MemoryFileSystem::default());
+        fs.insert("assets/levels/entry.tbasset", vec![]);
+        fs.insert("assets/prefabs/ball.tbasset", vec![]);
+        vfs.mount("project", fs.clone());
+        let dirs = vec![PathBuf::from("project/assets")];
+
+        let mut database = AssetDatabase::default();
+        database.rebuild(&vfs, &dirs).unwrap();
+        assert_eq!(database.len(), 2);
+        let ball = Path::new("project/assets/prefabs/ball.tbasset");
+        let guid = database.guid_of(ball).unwrap();
+        assert!(vfs.exists(AssetMeta::path_of(ball)));
+
+        let meta = fs.remove("assets/prefabs/ball.tbasset.meta").unwrap();
+        fs.remove("assets/prefabs/ball.tbasset");
+        fs.insert("assets/ball.tbasset", vec![]);
+        fs.insert("assets/ball.tbasset.meta", meta);
+        database.rebuild(&vfs, &dirs).unwrap();
+        assert_eq!(database.len(), 2);
+        assert_eq!(
+            database.path_of(guid),
+            Some(Path::new("project/assets/ball.tbasset"))
+        );
+
+        let meta = fs.read(Path::new("assets/ball.tbasset.meta")).unwrap();
+        fs.insert("assets/copy.tbasset", vec![]);
+        fs.insert("assets/copy.tbasset.meta", meta);
+        database.rebuild(&vfs, &dirs).unwrap();
+        assert_eq!(database.len(), 3);
+        assert_eq!(
+            database.path_of(guid),
+            Some(Path::new("project/assets/ball.tbasset"))
+        );
+    }
+
+    #[test]
+    fn read_only_mount() {
+        let mut packer = Packer::default();
+        packer.add_file("levels/entry.tbasset", vec![]).unwrap();
+        packer.add_file("levels/packed.tbasset", vec![]).unwrap();
+        packer
+            .add_file(
+                "levels/packed.tbasset.meta",
+                format!("guid = \"{}\"\n", AssetGuid::generate()).into_bytes(),
+            )
+            .unwrap();
+        let mut bytes = vec![];
+        packer.write(&mut bytes).unwrap();
+
+        let vfs = Vfs::default();
+        let fs = Arc::new(MemoryFileSystem::default());
+        fs.insert("assets/prefabs/ball.tbasset", vec![]);
+        vfs.mount("project", fs.clone());
+        vfs.mount(
+            "project/assets",
+            Arc::new(ArchiveFileSystem::from_bytes(bytes).unwrap()),
+        );
+        let dirs = vec![PathBuf::from("project/assets")];
+
+        let mut database = AssetDatabase::default();
+        database.rebuild(&vfs, &dirs).unwrap();
+        assert_eq!(database.len(), 3);
+        let entry = Path::new("project/assets/levels/entry.tbasset");
+        let packed = Path::new("project/assets/levels/packed.tbasset");
+        let ball = Path::new("project/assets/prefabs/ball.tbasset");
+        assert!(database.guid_of(entry).is_some());
+        assert_eq!(
+            database.guid_of(packed),
+            Some(AssetMeta::read(&vfs, packed).unwrap().guid)
+        );
+        assert!(vfs.is_read_only(entry));
+        assert!(!vfs.exists(AssetMeta::path_of(entry)));
+        assert!(!fs.exists(Path::new("assets/levels/entry.tbasset.meta")));
+        assert!(!vfs.is_read_only(ball));
+        assert!(vfs.exists(AssetMeta::path_of(ball)));
+    }
+}
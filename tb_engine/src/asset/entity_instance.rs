@@ -3,6 +3,9 @@ use std::path::PathBuf;
 use tb_core::serde::*;
 use tb_ecs::Component;
 
+use crate::asset::prefab::Prefab;
+use crate::asset::AssetHandle;
+
 #[derive(Serialize, Deserialize)]
 pub struct EntityInstance {
     header: EntityInstanceHeader,
@@ -23,9 +26,10 @@ enum PrefabOrInstance {
 #[serde_box]
 trait PrefabModifier: SerdeBoxSer + SerdeBoxDe {}
 
+/// An entity in a prefab, the prefab is referenced by guid to survive moving the file
 #[derive(Serialize, Deserialize)]
 struct EntityPath {
-    prefab_file: PathBuf,
+    prefab: AssetHandle<Prefab>,
     entity: PathBuf,
 }
 
@@ -0,0 +1,248 @@
+use std::path::Path;
+
+use tb_core::serde::de::DeserializeOwned;
+use tb_core::serde::{rmp_serde, serde_json, SerdeBox, Serialize};
+
+use crate::asset::errors::*;
+use crate::asset::Asset;
+use crate::path::TbPath;
+use crate::vfs::Vfs;
+
+/// Serialization format of asset files
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AssetFormat {
+    /// Readable json, for authoring
+    Json,
+    /// Compact MessagePack after `AssetFormat::BINARY_HEADER`, for shipping
+    Binary,
+}
+
+impl AssetFormat {
+    pub const ALL: [AssetFormat; 2] = [AssetFormat::Json, AssetFormat::Binary];
+    pub const BINARY_HEADER: &'static [u8; 4] = b"TBMP";
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AssetFormat::Json => "tbasset",
+            AssetFormat::Binary => "tbbin",
+        }
+    }
+
+    /// `None` if the extension of `path` is not of any format
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "tbasset" | "json" => Some(AssetFormat::Json),
+            "tbbin" => Some(AssetFormat::Binary),
+            _ => None,
+        }
+    }
+
+    /// The format to save `path` in, json if the extension is unknown
+    pub fn for_path(path: &Path) -> Self {
+        Self::from_extension(path).unwrap_or(AssetFormat::Json)
+    }
+
+    /// The format of `data` read from `path`, by the header first then by the extension
+    pub fn detect(path: &Path, data: &[u8]) -> Self {
+        if data.starts_with(Self::BINARY_HEADER) {
+            AssetFormat::Binary
+        } else {
+            Self::for_path(path)
+        }
+    }
+
+    pub fn serialize<T: ?Sized + Serialize>(&self, value: &T) -> Result<Vec<u8>> {
+        match self {
+            AssetFormat::Json => {
+                serde_json::to_vec_pretty(value).chain_err(|| "Failed to serialize json")
+            }
+            AssetFormat::Binary => {
+                let mut data = Self::BINARY_HEADER.to_vec();
+                // structs as maps keyed by the field names, like the json files
+                rmp_serde::encode::write_named(&mut data, value)
+                    .chain_err(|| "Failed to serialize MessagePack")?;
+                Ok(data)
+            }
+        }
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T> {
+        match self {
+            AssetFormat::Json => {
+                serde_json::from_slice(data).chain_err(|| "Failed to deserialize json")
+            }
+            AssetFormat::Binary => {
+                let data = data
+                    .strip_prefix(&Self::BINARY_HEADER[..])
+                    .ok_or_else(|| Error::from("Binary asset header not found"))?;
+                rmp_serde::from_slice(data).chain_err(|| "Failed to deserialize MessagePack")
+            }
+        }
+    }
+
+    /// Transcode `data` of this format to `to`
+    pub fn convert(&self, data: &[u8], to: AssetFormat) -> Result<Vec<u8>> {
+        if *self == to {
+            return Ok(data.to_vec());
+        }
+        let asset: SerdeBox<dyn Asset> = self.deserialize(data)?;
+        to.serialize(&asset)
+    }
+}
+
+/// Convert the asset file `source` to `dest`, in the formats detected by `AssetFormat`
+pub fn convert_file(source: &TbPath, dest: &TbPath) -> Result<()> {
+    let (source, dest) = (source.virtual_path(), dest.virtual_path());
+    let data = Vfs::get()
+        .read(&source)
+        .chain_err(|| format!("Failed to read asset file. path: {:?}", source))?;
+    let data = AssetFormat::detect(&source, &data)
+        .convert(&data, AssetFormat::for_path(&dest))
+        .chain_err(|| {
+            format!(
+                "Failed to convert asset. from: {:?}, to: {:?}",
+                source, dest
+            )
+        })?;
+    Vfs::get()
+        .write(&dest, &data)
+        .chain_err(|| format!("Failed to write asset file. path: {:?}", dest))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tb_core::serde::{serde_json, Deserialize, SerdeBox, Serialize};
+    use tb_ecs::*;
+
+    use crate::asset::format::AssetFormat;
+    use crate::asset::prefab::Prefab;
+    use crate::asset::Asset;
+    use crate::level::Level;
+    use crate::path::TbPath;
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Sample {
+        name: String,
+        path: TbPath,
+        values: Vec<f32>,
+        child: Option<Box<Sample>>,
+    }
+
+    #[component]
+    struct Transform {
+        position: (f64, f64),
+        scale: f32,
+    }
+
+    #[component]
+    struct Follow {
+        target: Entity,
+    }
+
+    #[test]
+    fn round_trip() {
+        let sample = Sample {
+            name: "root".to_string(),
+            path: TbPath::new_project_assets("levels/entry.tbasset"),
+            values: vec![0.5, -1.0],
+            child: Some(Box::new(Sample {
+                name: "child".to_string(),
+                path: TbPath::new_engine_assets("prefabs/ball.tbbin"),
+                values: vec![],
+                child: None,
+            })),
+        };
+        for format in AssetFormat::ALL.iter() {
+            let data = format.serialize(&sample).unwrap();
+            let path = Path::new("a").with_extension(format.extension());
+            assert_eq!(AssetFormat::detect(&path, &data), *format);
+            assert_eq!(AssetFormat::detect(Path::new("a"), &data), *format);
+            assert_eq!(format.deserialize::<Sample>(&data).unwrap(), sample);
+
+            let prefab = format.serialize(&Prefab::default()).unwrap();
+            let deserialized: Prefab = format.deserialize(&prefab).unwrap();
+            assert_eq!(format.serialize(&deserialized).unwrap(), prefab);
+        }
+
+        let json = AssetFormat::Json.serialize(&sample).unwrap();
+        assert!(AssetFormat::Binary.deserialize::<Sample>(&json).is_err());
+        assert_eq!(
+            AssetFormat::Json.convert(&json, AssetFormat::Json).unwrap(),
+            json
+        );
+    }
+
+    #[test]
+    fn level_round_trip() {
+        let mut world = World::default();
+        let ball = world
+            .create_entity()
+            .with(Transform {
+                // not exact in binary floats, kept as written by `arbitrary_precision`
+                position: (0.1, -1e-300),
+                scale: 0.3,
+            })
+            .create();
+        world
+            .create_entity()
+            .with(Transform {
+                position: (f64::MAX, std::f64::consts::PI),
+                scale: f32::MIN_POSITIVE,
+            })
+            .with(Follow { target: ball })
+            .create();
+        let level = Level::from_world(&world).unwrap();
+        let snapshot = level.world().unwrap().clone();
+        assert_eq!(snapshot.components()[Transform::type_name()].len(), 2);
+        assert_eq!(snapshot.components()[Follow::type_name()].len(), 1);
+
+        let level: SerdeBox<dyn Asset> = SerdeBox(Box::new(level));
+        let snapshot_of = |asset: &SerdeBox<dyn Asset>| {
+            let level: &Level = asset.as_any().downcast_ref().unwrap();
+            level.world().unwrap().clone()
+        };
+        let mut saved = vec![];
+        for format in AssetFormat::ALL.iter() {
+            let data = format.serialize(&level).unwrap();
+            let deserialized: SerdeBox<dyn Asset> = format.deserialize(&data).unwrap();
+            assert_eq!(snapshot_of(&deserialized), snapshot);
+            assert_eq!(format.serialize(&deserialized).unwrap(), data);
+            saved.push((*format, data));
+        }
+
+        for (from, data) in &saved {
+            for (to, expected) in &saved {
+                assert_eq!(&from.convert(data, *to).unwrap(), expected);
+            }
+        }
+        let (_, json) = &saved[0];
+        let converted = AssetFormat::Binary
+            .convert(
+                &AssetFormat::Json
+                    .convert(json, AssetFormat::Binary)
+                    .unwrap(),
+                AssetFormat::Json,
+            )
+            .unwrap();
+        assert_eq!(&converted, json);
+        let text = std::str::from_utf8(json).unwrap();
+        assert!(text.contains("0.1") && text.contains("-1e-300"));
+        let (_, binary) = &saved[1];
+        assert!(binary.len() < serde_json::to_vec(&level).unwrap().len());
+
+        let binary = AssetFormat::Json
+            .convert(json, AssetFormat::Binary)
+            .unwrap();
+        let level: SerdeBox<dyn Asset> = AssetFormat::Binary.deserialize(&binary).unwrap();
+        let mut restored = World::default();
+        let link = restored.restore(&snapshot_of(&level)).unwrap();
+        let transforms = unsafe { restored.fetch_components::<Transform>() };
+        let transform = transforms.get(link.get(ball).unwrap()).unwrap();
+        assert_eq!(transform.position, (0.1, -1e-300));
+        assert_eq!(transform.scale, 0.3);
+        let follows = unsafe { restored.fetch_components::<Follow>() };
+        assert_eq!(follows.iter().count(), 1);
+    }
+}
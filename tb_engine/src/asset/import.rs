@@ -0,0 +1,255 @@
+use std::path::{Path, PathBuf};
+
+use tb_core::hash::fnv1a_64;
+use tb_core::serde::SerdeBox;
+use tb_ecs::inventory;
+
+use crate::app_info::AppInfo;
+use crate::asset::errors::*;
+use crate::asset::format::AssetFormat;
+use crate::asset::Asset;
+use crate::vfs::Vfs;
+
+/// Turns a source file into a runtime `Asset`.
+/// Register it by `inventory::submit!` of an `AssetImporterInfo`
+pub trait AssetImporter: Send + Sync {
+    /// Bump it when the imported result changes, so the cached results are imported again
+    fn version(&self) -> u32;
+
+    fn import(&self, path: &Path, data: &[u8]) -> Result<Box<dyn Asset>>;
+}
+
+pub struct AssetImporterInfo {
+    name: &'static str,
+    extensions: &'static [&'static str],
+    importer: Box<dyn AssetImporter>,
+}
+
+impl AssetImporterInfo {
+    /// `extensions` of the source files are without the dot
+    pub fn new(
+        name: &'static str,
+        extensions: &'static [&'static str],
+        importer: impl AssetImporter + 'static,
+    ) -> Self {
+        Self {
+            name,
+            extensions,
+            importer: Box::new(importer),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn extensions(&self) -> &'static [&'static str] {
+        self.extensions
+    }
+
+    pub fn importer(&self) -> &dyn AssetImporter {
+        self.importer.as_ref()
+    }
+
+    /// The registered importer of the extension of `path`
+    pub fn find(path: &Path) -> Option<&'static AssetImporterInfo> {
+        let extension = path.extension()?.to_str()?;
+        inventory::iter::<AssetImporterInfo>
+            .into_iter()
+            .find(|info| {
+                info.extensions
+                    .iter()
+                    .any(|ext| ext.eq_ignore_ascii_case(extension))
+            })
+    }
+}
+
+inventory::collect!(AssetImporterInfo);
+
+/// Imported assets cached in the binary format, one file for each source path.
+/// A cached asset is stale if the hash of the source or the name and version of the importer changed,
+/// the next import replaces it
+pub struct ImportCache {
+    vfs: &'static Vfs,
+    dir: PathBuf,
+}
+
+impl ImportCache {
+    pub fn new(vfs: &'static Vfs, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            vfs,
+            dir: dir.into(),
+        }
+    }
+
+    /// The cache in the cache dir of the project mounted in `vfs`
+    pub fn project(vfs: &'static Vfs) -> Self {
+        Self::new(
+            vfs,
+            Path::new(Vfs::PROJECT_MOUNT_POINT)
+                .join(AppInfo::cache_dir_name())
+                .join("imported"),
+        )
+    }
+
+    /// The cache file of the source at `path`
+    pub fn cache_path(&self, info: &AssetImporterInfo, path: &Path) -> PathBuf {
+        self.dir.join(format!(
+            "{}-{:016x}.{}",
+            info.name,
+            fnv1a_64(path.to_string_lossy().as_bytes()),
+            AssetFormat::Binary.extension()
+        ))
+    }
+
+    /// Written before the cached asset, to find out if it's stale
+    fn source_key(info: &AssetImporterInfo, data: &[u8]) -> [u8; 8] {
+        fnv1a_64(
+            &[
+                &fnv1a_64(data).to_le_bytes()[..],
+                info.name.as_bytes(),
+                &info.importer.version().to_le_bytes(),
+            ]
+            .concat(),
+        )
+        .to_le_bytes()
+    }
+
+    /// Import `data` read from `path`, the cached result is used unless it's stale
+    pub fn import(
+        &self,
+        info: &AssetImporterInfo,
+        path: &Path,
+        data: &[u8],
+    ) -> Result<SerdeBox<dyn Asset>> {
+        let cache_path = self.cache_path(info, path);
+        let key = Self::source_key(info, data);
+        if let Ok(cached) = self.vfs.read(&cache_path) {
+            if let Some(cached) = cached.strip_prefix(&key[..]) {
+                match AssetFormat::Binary.deserialize(cached) {
+                    Ok(asset) => return Ok(asset),
+                    Err(e) => log_warn!("{}", e.display_chain()),
+                }
+            }
+        }
+
+        let asset = SerdeBox(info.importer.import(path, data).chain_err(|| {
+            format!(
+                "Failed to import asset. path: {:?}, importer: {}",
+                path, info.name
+            )
+        })?);
+        // failing to cache only costs another import
+        let cached = AssetFormat::Binary.serialize(&asset).and_then(|cached| {
+            self.vfs
+                .write(&cache_path, &[&key[..], &cached].concat())
+                .chain_err(|| format!("Failed to write import cache. path: {:?}", cache_path))
+        });
+        if let Err(e) = cached {
+            log_warn!("{}", e.display_chain());
+        }
+        Ok(asset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::asset::errors::*;
+    use crate::asset::import::{AssetImporter, AssetImporterInfo, ImportCache};
+    use crate::asset::table::Table;
+    use crate::asset::Asset;
+    use crate::vfs::{MemoryFileSystem, Vfs};
+
+    struct CountingImporter {
+        version: u32,
+        imports: Arc<AtomicUsize>,
+    }
+
+    impl AssetImporter for CountingImporter {
+        fn version(&self) -> u32 {
+            self.version
+        }
+
+        fn import(&self, _path: &Path, data: &[u8]) -> Result<Box<dyn Asset>> {
+            self.imports.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(Table {
+                rows: vec![vec![String::from_utf8_lossy(data).into_owned()]],
+            }))
+        }
+    }
+
+    fn cache() -> ImportCache {
+        let vfs: &'static Vfs = Box::leak(Box::new(Vfs::default()));
+        vfs.mount("cache", Arc::new(MemoryFileSystem::default()));
+        ImportCache::new(vfs, "cache")
+    }
+
+    #[test]
+    fn import_csv() {
+        let info = AssetImporterInfo::find(Path::new("tables/items.CSV")).unwrap();
+        assert_eq!(info.name(), "csv");
+        assert!(AssetImporterInfo::find(Path::new("levels/entry.tbasset")).is_none());
+
+        let cache = cache();
+        let data = b"name,damage\nsword,3\n";
+        let path = Path::new("tables/items.csv");
+        assert_eq!(cache.cache_path(info, path), cache.cache_path(info, path));
+        assert_ne!(
+            cache.cache_path(info, path),
+            cache.cache_path(info, Path::new("tables/other.csv"))
+        );
+
+        let asset = cache
+            .import(info, Path::new("tables/items.csv"), data)
+            .unwrap();
+        assert_eq!(
+            asset.as_any().downcast_ref::<Table>().unwrap().rows,
+            vec![vec!["name", "damage"], vec!["sword", "3"]]
+        );
+    }
+
+    #[test]
+    fn reimport_when_stale() {
+        let imports = Arc::new(AtomicUsize::new(0));
+        let importer = |version| {
+            AssetImporterInfo::new(
+                "counting",
+                &["count"],
+                CountingImporter {
+                    version,
+                    imports: imports.clone(),
+                },
+            )
+        };
+        let (version1, version2) = (importer(1), importer(2));
+        let cache = cache();
+        let import_path = |info: &AssetImporterInfo, path: &str, data: &[u8]| {
+            let asset = cache.import(info, Path::new(path), data).unwrap();
+            let table = asset.as_any().downcast_ref::<Table>().unwrap();
+            assert_eq!(table.rows, vec![vec![String::from_utf8_lossy(data)]]);
+            imports.load(Ordering::SeqCst)
+        };
+        let import = |info: &AssetImporterInfo, data: &[u8]| import_path(info, "a.count", data);
+
+        assert_eq!(import(&version1, b"a"), 1);
+        // cache hit
+        assert_eq!(import(&version1, b"a"), 1);
+        // changed source
+        assert_eq!(import(&version1, b"b"), 2);
+        assert_eq!(import(&version1, b"b"), 2);
+        // changed importer version
+        assert_eq!(import(&version2, b"a"), 3);
+        assert_eq!(import(&version2, b"a"), 3);
+        // replaced by the import of version 2
+        assert_eq!(import(&version1, b"a"), 4);
+
+        // another source of the same data
+        assert_eq!(import_path(&version1, "b.count", b"a"), 5);
+        assert_eq!(import(&version1, b"a"), 5);
+        assert_eq!(cache.vfs.read_dir("cache").unwrap().len(), 2);
+    }
+}
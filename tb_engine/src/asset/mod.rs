@@ -1,20 +1,30 @@
 use std::any::Any;
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-use std::fs::File;
+use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver, Sender};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant, SystemTime};
 
 use errors::*;
+pub use errors::{Error as AssetError, ErrorKind as AssetErrorKind, Result as AssetResult};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tb_ecs::*;
 
+use crate::app_info::AppInfo;
+use crate::asset::database::{AssetDatabase, AssetGuid};
+use crate::asset::format::AssetFormat;
+use crate::asset::import::{AssetImporterInfo, ImportCache};
 use crate::path::TbPath;
+use crate::vfs::Vfs;
 
+pub mod database;
 pub mod entity_instance;
+pub mod format;
+pub mod import;
 pub mod prefab;
+pub mod table;
 
 mod errors {
     pub use tb_core::error::*;
@@ -22,36 +32,187 @@ mod errors {
     error_chain! {}
 }
 
-#[derive(Copy, Clone)]
+/// Id of the handles deserialized but not resolved by `AssetLoader::resolve` yet
+const UNRESOLVED_ID: u64 = u64::MAX;
+
+/// Keeps the asset loaded until all the strong handles of it are dropped.
+/// Serialized as the guid of the asset, deserialized as an unresolved handle
 pub struct AssetHandle<T> {
-    id: u64,
+    id: Arc<u64>,
+    guid: Option<AssetGuid>,
     _phantom: PhantomData<T>,
 }
 
+impl<T> AssetHandle<T> {
+    fn new(id: Arc<u64>, guid: Option<AssetGuid>) -> Self {
+        Self {
+            id,
+            guid,
+            _phantom: Default::default(),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        *self.id
+    }
+
+    /// `None` if the asset isn't in the assets dirs
+    pub fn guid(&self) -> Option<AssetGuid> {
+        self.guid
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        *self.id != UNRESOLVED_ID
+    }
+
+    pub fn downgrade(&self) -> WeakAssetHandle<T> {
+        WeakAssetHandle {
+            id: Arc::downgrade(&self.id),
+            guid: self.guid,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<T> Clone for AssetHandle<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.id.clone(), self.guid)
+    }
+}
+
+impl<T> Serialize for AssetHandle<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self.guid {
+            None => Err(serde::ser::Error::custom(
+                "Asset handle without guid can't be serialized",
+            )),
+            Some(guid) => guid.serialize(serializer),
+        }
+    }
+}
+
+impl<'de, T> Deserialize<'de> for AssetHandle<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let guid = AssetGuid::deserialize(deserializer)?;
+        Ok(Self::new(Arc::new(UNRESOLVED_ID), Some(guid)))
+    }
+}
+
+/// Refers to an asset without keeping it loaded
+pub struct WeakAssetHandle<T> {
+    id: Weak<u64>,
+    guid: Option<AssetGuid>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> WeakAssetHandle<T> {
+    /// `None` if the asset is collected
+    pub fn upgrade(&self) -> Option<AssetHandle<T>> {
+        self.id.upgrade().map(|id| AssetHandle::new(id, self.guid))
+    }
+}
+
+impl<T> Clone for WeakAssetHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            guid: self.guid,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+/// Sent by `AssetLoader`, carrying the id of the asset
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AssetEvent {
+    /// The asset is loaded and ready to `get`
+    Created(u64),
+    /// The file of a loaded asset was changed and the asset is reloaded
+    Modified(u64),
+    /// The file of the asset was removed and the asset is dropped
+    Removed(u64),
+    /// The asset passed to `AssetLoader::save` is written to its file
+    Saved(u64),
+    /// Loading or saving the asset failed, see `AssetLoader::state`
+    Failed(u64),
+}
+
+/// Load state of an asset, see `AssetLoader::state`
+#[derive(Debug)]
+pub enum AssetState<'a> {
+    Loading,
+    Loaded,
+    /// The last load or save failed, the data loaded before is still kept
+    Failed(&'a AssetError),
+    /// Never requested or removed
+    Unloaded,
+}
+
+/// Estimated memory of the loaded assets, see `AssetLoader::memory_report`
+#[derive(Clone, Debug, Default)]
+pub struct AssetMemoryReport {
+    /// Id and bytes of every loaded asset, the largest first
+    pub assets: Vec<(u64, usize)>,
+    pub total: usize,
+    pub budget: Option<usize>,
+}
+
+impl AssetMemoryReport {
+    pub fn is_over_budget(&self) -> bool {
+        self.budget.map_or(false, |budget| self.total > budget)
+    }
+}
+
 pub type AssetArc = Arc<SerdeBox<dyn Asset>>;
 
 #[serde_box]
-trait Asset: Any + Send + Sync + SerdeBoxSer + SerdeBoxDe {
+pub trait Asset: Any + Send + Sync + SerdeBoxSer + SerdeBoxDe {
     fn as_any(&self) -> &dyn Any;
+
+    /// Estimated bytes held by the asset, override to count the data on the heap
+    fn memory_size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
 }
 
+/// Background task of an asset sent back through the completed channel
+enum AssetTask {
+    Load,
+    /// The path saved to, and the number of the pending saves done by the task
+    Save(PathBuf, usize),
+}
+
+type PendingReceivers = (Receiver<PathBuf>, Receiver<(PathBuf, AssetArc)>);
+type PendingChannel = (
+    Sender<PathBuf>,
+    Sender<(PathBuf, AssetArc)>,
+    Arc<Mutex<PendingReceivers>>,
+);
+type CompletedTask = (u64, AssetTask, Result<AssetArc>);
+
 pub struct AssetLoader {
     id_to_assets: HashMap<u64, AssetArc>,
     path_to_ids: HashMap<PathBuf, u64>,
+    handles: HashMap<u64, Weak<u64>>,
     next_id: u64,
     threads: thread_pool::ThreadPool,
-    id_to_pending_channel: HashMap<
-        u64,
-        (
-            Sender<PathBuf>,
-            Sender<(PathBuf, AssetArc)>,
-            Arc<Mutex<(Receiver<PathBuf>, Receiver<(PathBuf, AssetArc)>)>>,
-        ),
-    >,
-    completed_assets_channel: (
-        Sender<(u64, Result<AssetArc>)>,
-        Receiver<(u64, Result<AssetArc>)>,
-    ),
+    id_to_pending_channel: HashMap<u64, PendingChannel>,
+    completed_assets_channel: (Sender<CompletedTask>, Receiver<CompletedTask>),
+    /// Loaded paths with their modification time when the load was requested
+    watched: HashMap<u64, (PathBuf, Option<SystemTime>)>,
+    loading: HashSet<u64>,
+    /// Number of the saves not completed yet of each asset, kept from `collect_unused` until they are done
+    saving: HashMap<u64, usize>,
+    failed: HashMap<u64, Error>,
+    hot_reload_interval: Option<Duration>,
+    last_poll: Instant,
+    collect_interval: Option<Duration>,
+    last_collect: Instant,
+    events: Events<AssetEvent>,
+    memory_budget: Option<usize>,
+    /// Built by scanning the assets dirs at the first use
+    database: Option<AssetDatabase>,
+    vfs: &'static Vfs,
 }
 
 ///
@@ -62,112 +223,362 @@ unsafe impl Sync for AssetLoader {}
 
 impl AssetLoader {
     pub fn load<T: Asset>(&mut self, path: TbPath) -> AssetHandle<T> {
-        let id = match self.path_to_ids.entry(path.into()) {
-            Entry::Occupied(occupied) => *occupied.get(),
-            Entry::Vacant(vacant) => {
-                let id = self.next_id;
-                vacant.insert(id);
-                self.next_id += 1;
-                let (pending_load_sender, _, pending_receiver) =
-                    self.get_or_new_pending_channel(id);
-                pending_load_sender.send(vacant.into_key());
-
-                let pending_receiver = pending_receiver.clone();
-                let completed_sender = self.completed_assets_channel.0.clone();
-                self.threads.execute(move || {
-                    Self::process_pending_task(id, pending_receiver, completed_sender)
-                });
-                id
+        self.load_path(path.virtual_path())
+    }
+
+    /// `None` if `guid` isn't found in the assets dirs
+    pub fn load_by_guid<T: Asset>(&mut self, guid: AssetGuid) -> Option<AssetHandle<T>> {
+        let path = self.database().path_of(guid)?.to_owned();
+        Some(self.load_path(path))
+    }
+
+    /// Load the asset of a deserialized handle by its guid, returns whether `handle` is resolved
+    pub fn resolve<T: Asset>(&mut self, handle: &mut AssetHandle<T>) -> bool {
+        if handle.is_resolved() {
+            return true;
+        }
+        match handle.guid.and_then(|guid| self.load_by_guid(guid)) {
+            None => false,
+            Some(loaded) => {
+                *handle = loaded;
+                true
+            }
+        }
+    }
+
+    /// Scan the assets dirs again for the guids, after the assets are moved
+    pub fn rebuild_database(&mut self) -> Result<()> {
+        self.database
+            .get_or_insert_with(Default::default)
+            .rebuild(self.vfs, &Self::asset_dirs())
+            .chain_err(|| "Failed to rebuild asset database")
+    }
+
+    fn database(&mut self) -> &mut AssetDatabase {
+        if self.database.is_none() {
+            if let Err(e) = self.rebuild_database() {
+                log_error!("{}", e.display_chain());
+            }
+        }
+        self.database.get_or_insert_with(Default::default)
+    }
+
+    /// The engine and project assets dirs scanned into the database
+    fn asset_dirs() -> Vec<PathBuf> {
+        [Vfs::ENGINE_MOUNT_POINT, Vfs::PROJECT_MOUNT_POINT]
+            .iter()
+            .map(|point| Path::new(point).join(AppInfo::assets_dir_name()))
+            .collect()
+    }
+
+    fn load_path<T: Asset>(&mut self, path: PathBuf) -> AssetHandle<T> {
+        let vfs = self.vfs;
+        let in_asset_dirs = Self::asset_dirs().iter().any(|dir| path.starts_with(dir));
+        // files added or moved since the last scan are registered as saved ones
+        let guid = if in_asset_dirs && vfs.exists(&path) {
+            match self.database().register(vfs, &path) {
+                Ok(guid) => Some(guid),
+                Err(e) => {
+                    log_error!("{}", e.display_chain());
+                    None
+                }
             }
+        } else {
+            self.database().guid_of(&path)
         };
-        AssetHandle {
-            id,
-            _phantom: Default::default(),
+        let (id, created) = self.handle_of_path(&path);
+        if created {
+            self.watched
+                .insert(*id, (path.clone(), self.vfs.modified(&path)));
+            self.request_load(*id, path);
         }
+        AssetHandle::new(id, guid)
     }
 
+    /// The asset is available by `get` at once, and written to `path` on the thread pool.
+    /// `AssetEvent::Saved` or `AssetEvent::Failed` is sent when it's done
     pub fn save<T: Asset>(&mut self, path: TbPath, asset: Box<T>) -> AssetHandle<T> {
-        let id = match self.path_to_ids.entry(path.into()) {
-            Entry::Occupied(occupied) => *occupied.get(),
-            Entry::Vacant(vacant) => {
-                let id = self.next_id;
-                vacant.insert(id);
-                self.next_id += 1;
-                id
+        let path = path.virtual_path();
+        let vfs = self.vfs;
+        let guid = match self.database().register(vfs, &path) {
+            Ok(guid) => Some(guid),
+            Err(e) => {
+                log_error!("{}", e.display_chain());
+                None
             }
         };
+        let (id, _) = self.handle_of_path(&path);
         let asset: AssetArc = Arc::new(SerdeBox(asset as Box<dyn Asset>));
-        self.id_to_assets.insert(id, asset.clone());
+        let event = match self.id_to_assets.insert(*id, asset.clone()) {
+            None => AssetEvent::Created(*id),
+            Some(_) => AssetEvent::Modified(*id),
+        };
+        self.events.send(event);
+        self.request_save(*id, path, asset);
 
-        AssetHandle {
-            id,
-            _phantom: Default::default(),
+        AssetHandle::new(id, guid)
+    }
+
+    /// Drop the asset now, even if it is still referenced by other handles
+    pub fn unload<T>(&mut self, handle: AssetHandle<T>) {
+        self.evict(*handle.id);
+    }
+
+    /// Drop the assets without strong handles, returns the number of them.
+    /// The assets being saved are kept until `AssetEvent::Saved` or `AssetEvent::Failed` is sent
+    pub fn collect_unused(&mut self) -> usize {
+        let saving = &self.saving;
+        let unused: Vec<u64> = self
+            .handles
+            .iter()
+            .filter(|(id, handle)| handle.strong_count() == 0 && !saving.contains_key(id))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &unused {
+            self.evict(*id);
         }
+        unused.len()
     }
 
-    pub fn update(&mut self) {
-        self.completed_assets_channel
-            .1
-            .try_iter()
-            .for_each(|(id, asset)| {
-                match asset {
-                    Ok(asset) => {
-                        self.id_to_assets.insert(id, asset);
-                    }
-                    Err(e) => {
-                        eprintln!("{}", e.display_chain());
-                        self.id_to_assets.remove(&id);
-                        return;
-                    }
-                };
+    /// Bytes of the loaded assets reported over the budget by `memory_report`, `None` for no budget
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+        self.memory_budget = budget;
+    }
+
+    pub fn memory_report(&self) -> AssetMemoryReport {
+        let mut assets: Vec<(u64, usize)> = self
+            .id_to_assets
+            .iter()
+            .map(|(id, asset)| (*id, asset.memory_size()))
+            .collect();
+        assets.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        AssetMemoryReport {
+            total: assets.iter().map(|(_, size)| size).sum(),
+            assets,
+            budget: self.memory_budget,
+        }
+    }
+
+    /// The strong handle of `path`, and whether the path is new to the loader
+    fn handle_of_path(&mut self, path: &Path) -> (Arc<u64>, bool) {
+        if let Some(id) = self.path_to_ids.get(path) {
+            let weak = self.handles.get_mut(id).unwrap();
+            let handle = weak.upgrade().unwrap_or_else(|| {
+                let handle = Arc::new(*id);
+                *weak = Arc::downgrade(&handle);
+                handle
             });
+            return (handle, false);
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        let handle = Arc::new(id);
+        self.handles.insert(id, Arc::downgrade(&handle));
+        self.path_to_ids.insert(path.to_owned(), id);
+        (handle, true)
     }
 
-    pub fn get<T: 'static>(&self, handle: AssetHandle<T>) -> Option<&T> {
-        match self.id_to_assets.get(&handle.id) {
+    fn evict(&mut self, id: u64) {
+        self.handles.remove(&id);
+        self.path_to_ids.retain(|_, path_id| *path_id != id);
+        self.watched.remove(&id);
+        self.loading.remove(&id);
+        self.saving.remove(&id);
+        self.failed.remove(&id);
+        self.id_to_pending_channel.remove(&id);
+        if self.id_to_assets.remove(&id).is_some() {
+            self.events.send(AssetEvent::Removed(id));
+        }
+    }
+
+    pub fn update(&mut self) {
+        // events nobody registered to read during the last frame are dropped
+        self.events.flush();
+        let completed: Vec<_> = self.completed_assets_channel.1.try_iter().collect();
+        for (id, task, asset) in completed {
+            // evicted while loading or saving
+            if !self.handles.contains_key(&id) {
+                continue;
+            }
+            self.loading.remove(&id);
+            if let AssetTask::Save(_, count) = task {
+                self.finish_save(id, count);
+            }
+            match (task, asset) {
+                (AssetTask::Load, Ok(asset)) => {
+                    self.failed.remove(&id);
+                    let event = match self.id_to_assets.insert(id, asset) {
+                        None => AssetEvent::Created(id),
+                        Some(_) => AssetEvent::Modified(id),
+                    };
+                    self.events.send(event);
+                }
+                (AssetTask::Save(path, _), Ok(_)) => {
+                    self.failed.remove(&id);
+                    // watch the saved file from its new modification time, so it's not reloaded
+                    let modified = self.vfs.modified(&path);
+                    self.watched.insert(id, (path, modified));
+                    self.events.send(AssetEvent::Saved(id));
+                }
+                (_, Err(e)) => {
+                    log_error!("{}", e.display_chain());
+                    self.failed.insert(id, e);
+                    self.events.send(AssetEvent::Failed(id));
+                }
+            };
+        }
+
+        if let Some(interval) = self.hot_reload_interval {
+            if self.last_poll.elapsed() >= interval {
+                self.last_poll = Instant::now();
+                self.reload_modified();
+            }
+        }
+
+        if let Some(interval) = self.collect_interval {
+            if self.last_collect.elapsed() >= interval {
+                self.last_collect = Instant::now();
+                self.collect_unused();
+            }
+        }
+    }
+
+    /// How often `update` calls `collect_unused`, `None` to only collect explicitly
+    pub fn set_collect_interval(&mut self, interval: Option<Duration>) {
+        self.collect_interval = interval;
+    }
+
+    pub fn state<T>(&self, handle: &AssetHandle<T>) -> AssetState<'_> {
+        let id = handle.id();
+        if let Some(e) = self.failed.get(&id) {
+            AssetState::Failed(e)
+        } else if self.id_to_assets.contains_key(&id) {
+            AssetState::Loaded
+        } else if self.loading.contains(&id) {
+            AssetState::Loading
+        } else {
+            AssetState::Unloaded
+        }
+    }
+
+    /// Poll the modification time of the loaded files every `interval`,
+    /// the changed ones are reloaded and `AssetEvent::Modified` is sent,
+    /// the removed ones are dropped and `AssetEvent::Removed` is sent.
+    /// `None` disables hot reload
+    pub fn set_hot_reload(&mut self, interval: Option<Duration>) {
+        self.hot_reload_interval = interval;
+    }
+
+    /// The events sent before a reader's first read are kept until the next `update`
+    pub fn events(&self) -> &Events<AssetEvent> {
+        &self.events
+    }
+
+    fn reload_modified(&mut self) {
+        let mut modified = vec![];
+        let mut removed = vec![];
+        for (id, (path, last_modified)) in &mut self.watched {
+            // checked again when the save is done, not to reload the file being written
+            if self.saving.contains_key(id) {
+                continue;
+            }
+            let current = self.vfs.modified(&path);
+            if current == *last_modified {
+                continue;
+            }
+            match current {
+                Some(_) => modified.push((*id, path.clone())),
+                None => removed.push(*id),
+            }
+            *last_modified = current;
+        }
+        for (id, path) in modified {
+            self.request_load(id, path);
+        }
+        for id in removed {
+            self.failed.remove(&id);
+            if self.id_to_assets.remove(&id).is_some() {
+                self.events.send(AssetEvent::Removed(id));
+            }
+        }
+    }
+
+    fn request_load(&mut self, id: u64, path: PathBuf) {
+        self.loading.insert(id);
+        let (pending_load_sender, _, _) = self.get_or_new_pending_channel(id);
+        let _ = pending_load_sender.send(path);
+        self.execute_pending_task(id);
+    }
+
+    fn request_save(&mut self, id: u64, path: PathBuf, asset: AssetArc) {
+        *self.saving.entry(id).or_default() += 1;
+        let (_, pending_save_sender, _) = self.get_or_new_pending_channel(id);
+        let _ = pending_save_sender.send((path, asset));
+        self.execute_pending_task(id);
+    }
+
+    fn finish_save(&mut self, id: u64, count: usize) {
+        if let Some(saving) = self.saving.get_mut(&id) {
+            *saving = saving.saturating_sub(count);
+            if *saving == 0 {
+                self.saving.remove(&id);
+            }
+        }
+    }
+
+    fn execute_pending_task(&mut self, id: u64) {
+        let pending_receiver = self.get_or_new_pending_channel(id).2.clone();
+        let completed_sender = self.completed_assets_channel.0.clone();
+        let vfs = self.vfs;
+        self.threads.execute(move || {
+            Self::process_pending_task(vfs, id, pending_receiver, completed_sender)
+        });
+    }
+
+    pub fn get<T: 'static>(&self, handle: &AssetHandle<T>) -> Option<&T> {
+        match self.id_to_assets.get(&handle.id()) {
             None => None,
             Some(asset) => asset.as_any().downcast_ref(),
         }
     }
 
-    fn save_block(path: impl AsRef<Path>, asset: AssetArc) -> Result<AssetArc> {
+    fn save_block(vfs: &Vfs, path: impl AsRef<Path>, asset: AssetArc) -> Result<AssetArc> {
         let path = path.as_ref();
-        let file = match Self::open_file(path) {
-            Ok(file) => file,
-            Err(e) => {
-                return Err(e);
-            }
-        };
-        serde_json::to_writer(file, asset.deref())
+        let data = AssetFormat::for_path(path)
+            .serialize(asset.deref())
             .chain_err(|| format!("Failed to serialize asset. path: {:?}", path))?;
+        vfs.write(path, &data)
+            .chain_err(|| format!("Failed to write asset file. path: {:?}", path))?;
         Ok(asset)
     }
 
-    fn load_block(path: impl AsRef<Path>) -> Result<AssetArc> {
+    fn load_block(vfs: &'static Vfs, path: impl AsRef<Path>) -> Result<AssetArc> {
         let path = path.as_ref();
-        let file = Self::open_file(path)?;
-        let res: AssetArc = Arc::new(
-            serde_json::from_reader(file)
+        let data = vfs
+            .read(path)
+            .chain_err(|| format!("Failed to read asset file. path: {:?}", path))?;
+        let asset = match AssetImporterInfo::find(path) {
+            Some(importer) => ImportCache::project(vfs).import(importer, path, &data)?,
+            None => AssetFormat::detect(path, &data)
+                .deserialize(&data)
                 .chain_err(|| format!("Failed to deserialize asset. path: {:?}", path))?,
-        );
-        Ok(res)
-    }
-
-    fn open_file(path: &Path) -> Result<File> {
-        std::fs::File::open(path)
-            .chain_err(|| format!("Failed to open asset file. path: {:?}", path))
+        };
+        Ok(Arc::new(asset))
     }
 
+    /// Run the latest pending task of the asset, a pending save drops the pending loads
     fn process_pending_task(
+        vfs: &'static Vfs,
         id: u64,
-        pending_receiver: Arc<Mutex<(Receiver<PathBuf>, Receiver<(PathBuf, AssetArc)>)>>,
-        completed_sender: Sender<(u64, Result<AssetArc>)>,
+        pending_receiver: Arc<Mutex<PendingReceivers>>,
+        completed_sender: Sender<CompletedTask>,
     ) {
-        let mut pending_receiver = match pending_receiver.lock() {
+        let pending_receiver = match pending_receiver.lock() {
             Ok(receiver) => receiver,
             Err(e) => {
-                completed_sender.send((
+                let _ = completed_sender.send((
                     id,
+                    AssetTask::Load,
                     Err(Error::with_chain(
                         Error::from(e.to_string()),
                         "Failed to lock pending_receiver.",
@@ -177,25 +588,22 @@ impl AssetLoader {
             }
         };
         let (pending_load_receiver, pending_save_receiver) = pending_receiver.deref();
-        if let Some((path, asset)) = pending_save_receiver.try_iter().last() {
+        let mut saves = pending_save_receiver.try_iter();
+        if let Some(latest) = saves.next() {
+            let (count, (path, asset)) =
+                saves.fold((1, latest), |(count, _), save| (count + 1, save));
             pending_load_receiver.try_iter().last();
-            completed_sender.send((id, Self::save_block(path, asset)));
+            let saved = Self::save_block(vfs, &path, asset);
+            let _ = completed_sender.send((id, AssetTask::Save(path, count), saved));
             return;
         }
 
         if let Some(path) = pending_load_receiver.try_iter().last() {
-            completed_sender.send((id, Self::load_block(path)));
+            let _ = completed_sender.send((id, AssetTask::Load, Self::load_block(vfs, path)));
         }
     }
 
-    fn get_or_new_pending_channel(
-        &mut self,
-        id: u64,
-    ) -> &(
-        Sender<PathBuf>,
-        Sender<(PathBuf, AssetArc)>,
-        Arc<Mutex<(Receiver<PathBuf>, Receiver<(PathBuf, AssetArc)>)>>,
-    ) {
+    fn get_or_new_pending_channel(&mut self, id: u64) -> &PendingChannel {
         self.id_to_pending_channel.entry(id).or_insert_with(|| {
             let (pending_load_sender, pending_load_receiver) = channel();
             let (pending_save_sender, pending_save_receiver) = channel();
@@ -208,19 +616,39 @@ impl AssetLoader {
     }
 }
 
-impl Default for AssetLoader {
-    fn default() -> Self {
+impl AssetLoader {
+    /// A loader of the assets in `vfs`, `default` uses `Vfs::get`
+    pub fn new(vfs: &'static Vfs) -> Self {
         Self {
             id_to_assets: Default::default(),
             path_to_ids: Default::default(),
+            handles: Default::default(),
             next_id: 0,
             threads: Default::default(),
             id_to_pending_channel: Default::default(),
             completed_assets_channel: channel(),
+            watched: Default::default(),
+            loading: Default::default(),
+            saving: Default::default(),
+            failed: Default::default(),
+            hot_reload_interval: Some(Duration::from_secs(1)),
+            last_poll: Instant::now(),
+            collect_interval: Some(Duration::from_secs(1)),
+            last_collect: Instant::now(),
+            events: Default::default(),
+            memory_budget: None,
+            database: None,
+            vfs,
         }
     }
 }
 
+impl Default for AssetLoader {
+    fn default() -> Self {
+        Self::new(Vfs::get())
+    }
+}
+
 #[system]
 struct LoadAssetSystem {}
 
@@ -228,6 +656,312 @@ impl<'s> System<'s> for LoadAssetSystem {
     type SystemData = Write<'s, AssetLoader>;
 
     fn run(&mut self, mut asset_loader: Self::SystemData) {
-        asset_loader.update()
+        asset_loader.update();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use tb_core::serde::{serde_json, SerdeBox};
+    use tb_ecs::ReaderId;
+
+    use crate::asset::database::AssetGuid;
+    use crate::asset::format::AssetFormat;
+    use crate::asset::table::Table;
+    use crate::asset::{Asset, AssetEvent, AssetHandle, AssetLoader, AssetState, AssetTask};
+    use crate::path::TbPath;
+    use crate::vfs::{FileSystem, MemoryFileSystem, Vfs};
+
+    fn loader() -> AssetLoader {
+        let mut loader = AssetLoader::default();
+        loader.set_hot_reload(None);
+        loader.set_collect_interval(None);
+        loader
+    }
+
+    /// A loader of a memory file system mounted as the project
+    fn memory_loader() -> (AssetLoader, Arc<MemoryFileSystem>) {
+        let vfs: &'static Vfs = Box::leak(Box::new(Vfs::default()));
+        let fs = Arc::new(MemoryFileSystem::default());
+        vfs.mount(Vfs::PROJECT_MOUNT_POINT, fs.clone());
+        let mut loader = AssetLoader::new(vfs);
+        loader.set_hot_reload(None);
+        loader.set_collect_interval(None);
+        (loader, fs)
+    }
+
+    fn table_data(cell: &str) -> Vec<u8> {
+        let table: SerdeBox<dyn Asset> = SerdeBox(Box::new(Table {
+            rows: vec![vec![cell.into()]],
+        }));
+        AssetFormat::Json.serialize(&table).unwrap()
+    }
+
+    /// Update until `event` is sent, returns the events sent until then
+    fn update_until(
+        loader: &mut AssetLoader,
+        reader: &mut ReaderId<AssetEvent>,
+        event: AssetEvent,
+    ) -> Vec<AssetEvent> {
+        let mut events = vec![];
+        let start = Instant::now();
+        while !events.contains(&event) {
+            assert!(start.elapsed() < Duration::from_secs(10), "{:?}", events);
+            loader.update();
+            events.extend(loader.events().read(reader).copied());
+            std::thread::yield_now();
+        }
+        events
+    }
+
+    #[test]
+    fn state_and_events() {
+        let mut loader = loader();
+        let mut reader = ReaderId::default();
+        let handle = AssetHandle::<()>::new(loader.handle_of_path(Path::new("a")).0, None);
+        assert!(matches!(loader.state(&handle), AssetState::Unloaded));
+
+        loader.loading.insert(handle.id());
+        assert!(matches!(loader.state(&handle), AssetState::Loading));
+        loader.update();
+        assert!(matches!(loader.state(&handle), AssetState::Loading));
+        assert_eq!(loader.events().read(&mut reader).count(), 0);
+
+        loader
+            .completed_assets_channel
+            .0
+            .send((handle.id(), AssetTask::Load, Err("broken".into())))
+            .unwrap();
+        loader.update();
+        match loader.state(&handle) {
+            AssetState::Failed(e) => assert_eq!(e.to_string(), "broken"),
+            state => panic!("unexpected state {:?}", state),
+        }
+        assert_eq!(
+            loader
+                .events()
+                .read(&mut reader)
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![AssetEvent::Failed(handle.id())]
+        );
+    }
+
+    #[test]
+    fn collect_unused() {
+        let mut loader = loader();
+        let (id, created) = loader.handle_of_path(Path::new("a"));
+        assert!(created);
+        let handle = AssetHandle::<()>::new(id, None);
+        let weak = handle.downgrade();
+        let cloned = handle.clone();
+        assert!(!loader.handle_of_path(Path::new("a")).1);
+
+        drop(handle);
+        assert_eq!(loader.collect_unused(), 0);
+        assert!(weak.upgrade().is_some());
+
+        drop(cloned);
+        assert_eq!(loader.collect_unused(), 1);
+        assert!(weak.upgrade().is_none());
+        assert!(loader.handle_of_path(Path::new("a")).1);
+
+        let handle = AssetHandle::<()>::new(loader.handle_of_path(Path::new("b")).0, None);
+        loader.failed.insert(handle.id(), "broken".into());
+        let id = handle.id();
+        loader.unload(handle.clone());
+        assert!(matches!(loader.state(&handle), AssetState::Unloaded));
+        assert!(!loader.handles.contains_key(&id));
+
+        let report = loader.memory_report();
+        assert_eq!(report.total, 0);
+        assert!(!report.is_over_budget());
+    }
+
+    #[test]
+    fn save_then_drop_handle() {
+        let mut loader = loader();
+        loader.set_collect_interval(Some(Duration::from_secs(0)));
+        // saving out of the assets dirs, no need to scan them
+        loader.database = Some(Default::default());
+        let mut reader = ReaderId::default();
+        assert_eq!(loader.events().read(&mut reader).count(), 0);
+        let dir = std::env::temp_dir().join(format!("tb_asset_save_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("table.json");
+
+        let handle = loader.save(
+            TbPath::new_absolute(&path),
+            Box::new(Table {
+                rows: vec![vec!["cell".into()]],
+            }),
+        );
+        let id = handle.id();
+        drop(handle);
+        // not collected before the save is done
+        assert_eq!(loader.collect_unused(), 0);
+
+        let mut events = vec![];
+        let start = Instant::now();
+        while !events.contains(&AssetEvent::Removed(id)) {
+            assert!(start.elapsed() < Duration::from_secs(10), "{:?}", events);
+            loader.update();
+            events.extend(loader.events().read(&mut reader).copied());
+            std::thread::yield_now();
+        }
+        assert_eq!(
+            events,
+            vec![
+                AssetEvent::Created(id),
+                AssetEvent::Saved(id),
+                AssetEvent::Removed(id)
+            ]
+        );
+        assert!(path.is_file());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn save_twice() {
+        let mut loader = loader();
+        loader.database = Some(Default::default());
+        let mut reader = ReaderId::default();
+        assert_eq!(loader.events().read(&mut reader).count(), 0);
+        let dir = std::env::temp_dir().join(format!("tb_asset_save_twice_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("table.json");
+        let table = |cell: &str| {
+            Box::new(Table {
+                rows: vec![vec![cell.into()]],
+            })
+        };
+
+        // both saves are pending when the first task runs
+        let id = *loader.handle_of_path(&path).0;
+        let pending = loader.get_or_new_pending_channel(id).2.clone();
+        let guard = pending.lock().unwrap();
+        let handle = loader.save(TbPath::new_absolute(&path), table("first"));
+        loader.save(TbPath::new_absolute(&path), table("second"));
+        assert_eq!(loader.saving[&id], 2);
+        drop(guard);
+
+        let start = Instant::now();
+        while loader.saving.contains_key(&id) {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            loader.update();
+            std::thread::yield_now();
+        }
+        let events: Vec<_> = loader.events().read(&mut reader).copied().collect();
+        assert_eq!(
+            events,
+            vec![
+                AssetEvent::Created(id),
+                AssetEvent::Modified(id),
+                AssetEvent::Saved(id)
+            ]
+        );
+        assert_eq!(loader.get(&handle).unwrap().rows[0][0], "second");
+        drop(handle);
+        assert_eq!(loader.collect_unused(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hot_reload() {
+        let (mut loader, fs) = memory_loader();
+        let mut reader = ReaderId::default();
+        assert_eq!(loader.events().read(&mut reader).count(), 0);
+        fs.insert("assets/table.tbasset", table_data("first"));
+
+        let handle = loader.load::<Table>(TbPath::new_project_assets("table.tbasset"));
+        let id = handle.id();
+        update_until(&mut loader, &mut reader, AssetEvent::Created(id));
+        assert_eq!(loader.get(&handle).unwrap().rows[0][0], "first");
+
+        loader.reload_modified();
+        assert!(!loader.loading.contains(&id));
+        fs.insert("assets/table.tbasset", table_data("second"));
+        loader.reload_modified();
+        update_until(&mut loader, &mut reader, AssetEvent::Modified(id));
+        assert_eq!(loader.get(&handle).unwrap().rows[0][0], "second");
+
+        // the file written by a save not completed yet is not reloaded
+        let modified = fs.modified(Path::new("assets/table.tbasset"));
+        loader.save(
+            TbPath::new_project_assets("table.tbasset"),
+            Box::new(Table {
+                rows: vec![vec!["saved".into()]],
+            }),
+        );
+        let start = Instant::now();
+        while fs.modified(Path::new("assets/table.tbasset")) == modified {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            std::thread::yield_now();
+        }
+        loader.reload_modified();
+        assert!(!loader.loading.contains(&id));
+        update_until(&mut loader, &mut reader, AssetEvent::Saved(id));
+        loader.reload_modified();
+        assert!(!loader.loading.contains(&id));
+
+        fs.remove("assets/table.tbasset");
+        loader.reload_modified();
+        assert_eq!(
+            loader
+                .events()
+                .read(&mut reader)
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![AssetEvent::Removed(id)]
+        );
+        assert!(matches!(loader.state(&handle), AssetState::Unloaded));
+    }
+
+    #[test]
+    fn load_after_move() {
+        let (mut loader, fs) = memory_loader();
+        fs.insert("assets/table.tbasset", table_data("cell"));
+        let handle = loader.load::<Table>(TbPath::new_project_assets("table.tbasset"));
+        let guid = handle.guid().unwrap();
+        drop(handle);
+        loader.collect_unused();
+
+        let meta = fs.remove("assets/table.tbasset.meta").unwrap();
+        let data = fs.remove("assets/table.tbasset").unwrap();
+        fs.insert("assets/moved/table.tbasset", data);
+        fs.insert("assets/moved/table.tbasset.meta", meta);
+
+        let moved = loader.load::<Table>(TbPath::new_project_assets("moved/table.tbasset"));
+        assert_eq!(moved.guid(), Some(guid));
+        assert_eq!(loader.load_by_guid::<Table>(guid).unwrap().id(), moved.id());
+        let mut deserialized: AssetHandle<Table> =
+            serde_json::from_str(&serde_json::to_string(&moved).unwrap()).unwrap();
+        assert!(loader.resolve(&mut deserialized));
+        assert_eq!(deserialized.id(), moved.id());
+        assert!(loader
+            .load_by_guid::<Table>(AssetGuid::generate())
+            .is_none());
+    }
+
+    #[test]
+    fn serialize_by_guid() {
+        let guid = AssetGuid::generate();
+        let handle = AssetHandle::<()>::new(Arc::new(0), Some(guid));
+        let json = serde_json::to_string(&handle).unwrap();
+        assert_eq!(json, format!("\"{}\"", guid));
+        let deserialized: AssetHandle<()> = serde_json::from_str(&json).unwrap();
+        assert!(!deserialized.is_resolved());
+        assert_eq!(deserialized.guid(), Some(guid));
+        assert!(matches!(
+            loader().state(&deserialized),
+            AssetState::Unloaded
+        ));
+
+        let handle = AssetHandle::<()>::new(Arc::new(0), None);
+        assert!(serde_json::to_string(&handle).is_err());
     }
 }
@@ -0,0 +1,91 @@
+use std::any::Any;
+use std::path::Path;
+
+use tb_core::serde::{Deserialize, Serialize};
+use tb_ecs::inventory;
+
+use crate::asset::errors::*;
+use crate::asset::import::{AssetImporter, AssetImporterInfo};
+use crate::asset::Asset;
+
+/// Rows of text cells, imported from csv files
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Table {
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Asset for Table {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Comma separated cells, quoted by `"` with `""` escaping a quote. Blank lines are skipped
+struct CsvImporter;
+
+impl AssetImporter for CsvImporter {
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn import(&self, _path: &Path, data: &[u8]) -> Result<Box<dyn Asset>> {
+        let text = std::str::from_utf8(data).chain_err(|| "Csv is not utf8")?;
+        Ok(Box::new(Table {
+            rows: parse_csv(text)?,
+        }))
+    }
+}
+
+inventory::submit! {
+    AssetImporterInfo::new("csv", &["csv"], CsvImporter)
+}
+
+fn parse_csv(text: &str) -> Result<Vec<Vec<String>>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => cell.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut cell)),
+            (false, '\n') => {
+                if !row.is_empty() || !cell.is_empty() {
+                    row.push(std::mem::take(&mut cell));
+                    rows.push(std::mem::take(&mut row));
+                }
+            }
+            (false, '\r') => {}
+            (false, c) => cell.push(c),
+        }
+    }
+    if quoted {
+        bail!("Unclosed quote in csv");
+    }
+    if !row.is_empty() || !cell.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::table::parse_csv;
+
+    #[test]
+    fn csv() {
+        assert_eq!(
+            parse_csv("a,b\r\n\n\"c,\"\"d\"\"\",\nlast").unwrap(),
+            vec![vec!["a", "b"], vec!["c,\"d\"", ""], vec!["last"]]
+        );
+        assert!(parse_csv("\"open").is_err());
+    }
+}
@@ -7,11 +7,29 @@ pub struct Name {
     name: String,
 }
 
-#[component]
+#[component(on_remove = Parent::detach_from_children)]
 pub struct Parent {
     parent: Entity,
 }
 
+impl Parent {
+    fn detach_from_children(&self, entity: Entity, commands: &mut Commands) {
+        let parent = self.parent;
+        commands.push(move |world| {
+            let children_components =
+                match unsafe { world.try_fetch_mut::<ComponentStorage<Children>>() } {
+                    Ok(children_components) => children_components,
+                    Err(_) => {
+                        return;
+                    }
+                };
+            if let Some(children) = children_components.get_mut(parent) {
+                children.children.retain(|&child| child != entity);
+            }
+        });
+    }
+}
+
 #[component]
 pub struct Children {
     children: Vec<Entity>,
@@ -77,7 +95,7 @@ impl<'s> Iterator for RecursiveChildrenIter<'s> {
 mod tests {
     use tb_ecs::*;
 
-    use crate::hierarchy::{Children, RecursiveChildrenIter};
+    use crate::hierarchy::{Children, Parent, RecursiveChildrenIter};
 
     #[test]
     fn recursive_children_iter() {
@@ -130,4 +148,21 @@ mod tests {
                 .collect::<Vec<Entity>>()
         )
     }
+
+    #[test]
+    fn kill_child_detaches_from_parent() {
+        let mut world = World::default();
+        let parent = world.create_entity().create();
+        let child = world.create_entity().with(Parent { parent }).create();
+        world.insert_component(
+            parent,
+            Children {
+                children: vec![child],
+            },
+        );
+
+        world.kill(child);
+        let children_components = unsafe { world.fetch_components::<Children>() };
+        assert!(children_components.get(parent).unwrap().children.is_empty());
+    }
 }
@@ -1,10 +1,12 @@
+use std::any::Any;
+
 use serde::{Deserialize, Serialize};
 
 use errors::*;
 use tb_ecs::*;
 
 use crate::asset::prefab::Prefab;
-use crate::asset::AssetHandle;
+use crate::asset::{Asset, AssetHandle};
 use crate::path::TbPath;
 
 mod errors {
@@ -13,9 +15,12 @@ mod errors {
     error_chain! {}
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Default, Deserialize, Serialize)]
 pub struct Level {
     root: Prefab,
+    /// Entities and components of the level saved from a world
+    #[serde(default)]
+    world: Option<WorldSnapshot>,
 }
 
 impl Level {
@@ -23,8 +28,27 @@ impl Level {
         Ok(Self {
             root: Prefab::create(path, world, None)
                 .chain_err(|| "Failed to create root prefab.")?,
+            world: None,
+        })
+    }
+
+    /// Level of the live entities and the registered components of `world`
+    pub fn from_world(world: &World) -> Result<Self> {
+        Ok(Self {
+            root: Prefab::default(),
+            world: Some(world.snapshot().chain_err(|| "Failed to snapshot world")?),
         })
     }
+
+    pub fn world(&self) -> Option<&WorldSnapshot> {
+        self.world.as_ref()
+    }
+}
+
+impl Asset for Level {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 #[derive(Default)]
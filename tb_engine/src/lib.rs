@@ -1,7 +1,13 @@
 #![feature(once_cell)]
 
+#[macro_use]
+pub mod log;
+
+pub mod app_exit;
 pub mod app_info;
 pub mod asset;
 pub mod hierarchy;
 pub mod level;
 pub mod path;
+pub mod settings;
+pub mod vfs;
@@ -0,0 +1,95 @@
+use std::fmt::Arguments;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::settings::LogLevel;
+
+/// Messages of the levels above it are dropped, set by the app from `Settings::log_level`
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(LogLevel::Info as usize);
+
+const LEVELS: [LogLevel; 5] = [
+    LogLevel::Error,
+    LogLevel::Warn,
+    LogLevel::Info,
+    LogLevel::Debug,
+    LogLevel::Trace,
+];
+
+pub fn set_max_level(level: LogLevel) {
+    MAX_LEVEL.store(level as usize, Ordering::Relaxed);
+}
+
+pub fn max_level() -> LogLevel {
+    LEVELS[MAX_LEVEL.load(Ordering::Relaxed)]
+}
+
+pub fn enabled(level: LogLevel) -> bool {
+    level <= max_level()
+}
+
+/// Write to stderr if `level` is enabled, use the `log_*` macros instead
+#[doc(hidden)]
+pub fn write(level: LogLevel, module: &str, args: Arguments) {
+    if enabled(level) {
+        eprintln!("[{:?} {}] {}", level, module, args);
+    }
+}
+
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {
+        $crate::log::write($level, module_path!(), format_args!($($arg)+))
+    };
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::settings::LogLevel::Error, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::settings::LogLevel::Warn, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::settings::LogLevel::Info, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::settings::LogLevel::Debug, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! log_trace {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::settings::LogLevel::Trace, $($arg)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::log::{enabled, max_level, set_max_level};
+    use crate::settings::LogLevel;
+
+    #[test]
+    fn max_level_filters() {
+        let level = max_level();
+        set_max_level(LogLevel::Warn);
+        assert!(enabled(LogLevel::Error));
+        assert!(enabled(LogLevel::Warn));
+        assert!(!enabled(LogLevel::Info));
+        set_max_level(LogLevel::Trace);
+        assert!(enabled(LogLevel::Trace));
+        set_max_level(level);
+    }
+}
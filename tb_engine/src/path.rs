@@ -1,10 +1,39 @@
-use std::path::PathBuf;
+use std::fmt::{Display, Formatter};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use errors::*;
 use tb_core::path_util;
 
 use crate::app_info::AppInfo;
+use crate::vfs::Vfs;
+
+pub use errors::{Error as TbPathError, ErrorKind as TbPathErrorKind, Result as TbPathResult};
+
+mod errors {
+    pub use tb_core::error::*;
+
+    error_chain! {
+        errors {
+            InvalidUri(uri: String) {
+                description("Invalid TbPath uri"),
+                display("Invalid TbPath uri. uri: {}, expected: <scheme>://<path>", uri),
+            }
+            UnknownScheme(scheme: String) {
+                description("Unknown TbPath scheme"),
+                display("Unknown TbPath scheme. scheme: {}", scheme),
+            }
+            EscapeBase(path: std::path::PathBuf) {
+                description("Path escapes its base"),
+                display("Path escapes its base. path: {:?}", path),
+            }
+        }
+    }
+}
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 enum TbPathBase {
     Absolute,
     EngineRoot,
@@ -13,7 +42,30 @@ enum TbPathBase {
     ProjectAssets,
 }
 
-#[derive(Clone)]
+impl TbPathBase {
+    const ALL: [TbPathBase; 5] = [
+        TbPathBase::Absolute,
+        TbPathBase::EngineRoot,
+        TbPathBase::EngineAssets,
+        TbPathBase::ProjectRoot,
+        TbPathBase::ProjectAssets,
+    ];
+
+    fn scheme(self) -> &'static str {
+        match self {
+            TbPathBase::Absolute => "file",
+            TbPathBase::EngineRoot => "engine-root",
+            TbPathBase::EngineAssets => "engine",
+            TbPathBase::ProjectRoot => "project-root",
+            TbPathBase::ProjectAssets => "project",
+        }
+    }
+}
+
+/// Path relative to one of the engine or project dirs, written as `<scheme>://<path>`:
+/// `project://` and `engine://` for the assets dirs, `project-root://` and `engine-root://` for the root dirs
+/// and `file://` for absolute paths
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub struct TbPath {
     base: TbPathBase,
     path: PathBuf,
@@ -26,17 +78,94 @@ impl TbPath {
             path: path.into(),
         }
     }
-    pub fn to_absolute(&self) -> PathBuf {
+
+    pub fn new_engine_assets(path: impl Into<PathBuf>) -> Self {
+        Self {
+            base: TbPathBase::EngineAssets,
+            path: path.into(),
+        }
+    }
+
+    pub fn new_project_root(path: impl Into<PathBuf>) -> Self {
+        Self {
+            base: TbPathBase::ProjectRoot,
+            path: path.into(),
+        }
+    }
+
+    pub fn new_engine_root(path: impl Into<PathBuf>) -> Self {
+        Self {
+            base: TbPathBase::EngineRoot,
+            path: path.into(),
+        }
+    }
+
+    pub fn new_absolute(path: impl Into<PathBuf>) -> Self {
+        Self {
+            base: TbPathBase::Absolute,
+            path: path.into(),
+        }
+    }
+
+    /// The path relative to the base
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Resolve `.` and `..` lexically, fails if the path goes above its base
+    pub fn normalize(&self) -> Result<TbPath> {
+        let mut path = PathBuf::new();
+        for component in self.path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    let popped = match path.components().next_back() {
+                        Some(Component::Normal(_)) => path.pop(),
+                        _ => false,
+                    };
+                    if !popped {
+                        bail!(ErrorKind::EscapeBase(self.path.clone()));
+                    }
+                }
+                component => path.push(component),
+            }
+        }
+        Ok(TbPath {
+            base: self.base,
+            path,
+        })
+    }
+
+    /// Join `path` to this path and normalize the result
+    pub fn join(&self, path: impl AsRef<Path>) -> Result<TbPath> {
+        TbPath {
+            base: self.base,
+            path: self.path.join(path),
+        }
+        .normalize()
+    }
+
+    /// The path in the mount table of `Vfs`
+    pub fn virtual_path(&self) -> PathBuf {
         let base = match self.base {
             TbPathBase::Absolute => return self.path.clone(),
-            TbPathBase::EngineRoot => &AppInfo::get().engine_root_dir,
-            TbPathBase::EngineAssets => &AppInfo::get().engine_assets_dir,
-            TbPathBase::ProjectRoot => &AppInfo::get().project_root_dir,
-            TbPathBase::ProjectAssets => &AppInfo::get().project_assets_dir,
+            TbPathBase::EngineRoot => PathBuf::from(Vfs::ENGINE_MOUNT_POINT),
+            TbPathBase::EngineAssets => {
+                Path::new(Vfs::ENGINE_MOUNT_POINT).join(AppInfo::assets_dir_name())
+            }
+            TbPathBase::ProjectRoot => PathBuf::from(Vfs::PROJECT_MOUNT_POINT),
+            TbPathBase::ProjectAssets => {
+                Path::new(Vfs::PROJECT_MOUNT_POINT).join(AppInfo::assets_dir_name())
+            }
         };
         base.join(&self.path)
     }
 
+    /// The path on disk, `None` if it's not resolved to a directory by `Vfs`
+    pub fn to_absolute(&self) -> Option<PathBuf> {
+        Vfs::get().real_path(self.virtual_path())
+    }
+
     pub fn join_prefix_assets_based(&self, prefix: impl Into<PathBuf>) -> Option<TbPath> {
         self.to_assets_based().map(|mut assets_based| {
             let mut prefix = prefix.into();
@@ -79,15 +208,105 @@ impl TbPath {
     }
 }
 
-impl From<TbPath> for PathBuf {
-    fn from(from: TbPath) -> Self {
-        let base = match from.base {
-            TbPathBase::Absolute => return from.path,
-            TbPathBase::EngineRoot => &AppInfo::get().engine_root_dir,
-            TbPathBase::EngineAssets => &AppInfo::get().engine_assets_dir,
-            TbPathBase::ProjectRoot => &AppInfo::get().project_root_dir,
-            TbPathBase::ProjectAssets => &AppInfo::get().project_assets_dir,
-        };
-        base.join(from.path)
+impl Display for TbPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}://", self.base.scheme())?;
+        if let TbPathBase::Absolute = self.base {
+            return write!(f, "{}", self.path.display());
+        }
+        let mut components = self.path.components();
+        if let Some(first) = components.next() {
+            write!(f, "{}", first.as_os_str().to_string_lossy())?;
+        }
+        for component in components {
+            write!(f, "/{}", component.as_os_str().to_string_lossy())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for TbPath {
+    type Err = Error;
+
+    /// Parse `<scheme>://<path>` and normalize the path
+    fn from_str(uri: &str) -> Result<Self> {
+        let (scheme, path) = uri
+            .split_once("://")
+            .ok_or_else(|| Error::from(ErrorKind::InvalidUri(uri.to_string())))?;
+        let base = TbPathBase::ALL
+            .iter()
+            .copied()
+            .find(|base| base.scheme() == scheme)
+            .ok_or_else(|| Error::from(ErrorKind::UnknownScheme(scheme.to_string())))?;
+        let path = PathBuf::from(path);
+        if (base == TbPathBase::Absolute) != path.has_root() {
+            bail!(ErrorKind::InvalidUri(uri.to_string()));
+        }
+        TbPath { base, path }.normalize()
+    }
+}
+
+impl Serialize for TbPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TbPath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let uri = String::deserialize(deserializer)?;
+        uri.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tb_core::serde::serde_json;
+
+    use crate::path::{TbPath, TbPathErrorKind};
+
+    #[test]
+    fn uri() {
+        let path: TbPath = "project://levels/./sub/../entry.tbasset".parse().unwrap();
+        assert_eq!(path, TbPath::new_project_assets("levels/entry.tbasset"));
+        assert_eq!(path.to_string(), "project://levels/entry.tbasset");
+        assert_eq!(
+            "engine-root://assets/a.png".parse::<TbPath>().unwrap(),
+            TbPath::new_engine_root("assets/a.png")
+        );
+        assert_eq!(
+            "file:///tmp/a.png".parse::<TbPath>().unwrap().to_string(),
+            "file:///tmp/a.png"
+        );
+
+        assert!(matches!(
+            "levels/entry.tbasset".parse::<TbPath>().unwrap_err().kind(),
+            TbPathErrorKind::InvalidUri(_)
+        ));
+        assert!(matches!(
+            "http://a".parse::<TbPath>().unwrap_err().kind(),
+            TbPathErrorKind::UnknownScheme(_)
+        ));
+        assert!(matches!(
+            "project://levels/../../a"
+                .parse::<TbPath>()
+                .unwrap_err()
+                .kind(),
+            TbPathErrorKind::EscapeBase(_)
+        ));
+        assert!(path.join("../../../a").is_err());
+        assert_eq!(
+            path.join("../other.tbasset").unwrap().to_string(),
+            "project://levels/other.tbasset"
+        );
+    }
+
+    #[test]
+    fn serde() {
+        let path = TbPath::new_engine_assets("prefabs/ball.tbasset");
+        let json = serde_json::to_string(&path).unwrap();
+        assert_eq!(json, r#""engine://prefabs/ball.tbasset""#);
+        assert_eq!(serde_json::from_str::<TbPath>(&json).unwrap(), path);
+        assert!(serde_json::from_str::<TbPath>(r#""engine://../a""#).is_err());
     }
 }
@@ -0,0 +1,431 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+pub use errors::{
+    Error as SettingsError, ErrorKind as SettingsErrorKind, Result as SettingsResult,
+};
+
+use crate::path::TbPath;
+
+mod errors {
+    pub use tb_core::error::*;
+
+    error_chain! {
+        errors {
+            HelpRequested {
+                description("Help requested"),
+                display("Help requested"),
+            }
+            UnknownArgument(arg: String) {
+                description("Unknown argument"),
+                display("Unknown argument. argument: {}", arg),
+            }
+            MissingValue(arg: String) {
+                description("Missing value of argument"),
+                display("Missing value of argument. argument: {}", arg),
+            }
+            InvalidValue(name: String, value: String) {
+                description("Invalid value"),
+                display("Invalid value. name: {}, value: {}", name, value),
+            }
+        }
+    }
+}
+
+use errors::*;
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Default for LogLevel {
+    fn default() -> Self {
+        LogLevel::Info
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            _ => bail!(ErrorKind::InvalidValue(
+                "log level".to_string(),
+                s.to_string()
+            )),
+        }
+    }
+}
+
+/// Settings of the app, merged from the defaults, `toybox.toml` of the project,
+/// the environment variables and the command line, the later wins
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub name: String,
+    pub entry_level: TbPath,
+    pub fps: f32,
+    pub plugins: Vec<String>,
+    /// Dirs relative to the project root mounted as the project assets, the former wins
+    pub asset_dirs: Vec<PathBuf>,
+    /// Quit after running this number of frames
+    pub frames: Option<u64>,
+    /// Quit after running this number of seconds
+    pub time_limit: Option<f32>,
+    pub headless: bool,
+    pub log_level: LogLevel,
+}
+
+impl Settings {
+    pub const CONFIG_FILE_NAME: &'static str = "toybox.toml";
+    pub const ENV_PREFIX: &'static str = "TOYBOX_";
+    pub const USAGE: &'static str = "\
+USAGE:
+    toybox [OPTIONS]
+
+OPTIONS:
+    -p, --project <DIR>          Launch the project in DIR instead of the packed archive
+        --name <NAME>            Name of the project, the project dir name by default
+        --entry-level <URI>      Level loaded at start, e.g. project://levels/entry.tbasset
+        --fps <FPS>              Target frames per second
+        --plugin <NAME>          Load the plugin library, repeatable
+        --asset-dir <DIR>        Dir of the project assets relative to the project, repeatable
+        --frames <N>             Quit after N frames
+        --time-limit <SECONDS>   Quit after running for SECONDS
+        --headless               Run without window and without sleeping between frames
+        --log-level <LEVEL>      error, warn, info, debug or trace
+    -h, --help                   Print this message
+
+Every option can also be set by the environment variable TOYBOX_<OPTION>, e.g. TOYBOX_LOG_LEVEL=debug,
+with lists separated by ',' for TOYBOX_PLUGINS and TOYBOX_ASSET_DIRS,
+or by the fields of toybox.toml of the project, e.g. log_level = \"debug\" and plugins = [\"pong\"].
+The command line wins over the environment variables, which win over toybox.toml.";
+
+    /// Fill the unset fields of `layer` with the defaults
+    pub fn new(layer: SettingsLayer, default_name: impl Into<String>) -> Self {
+        Settings {
+            name: layer.name.unwrap_or_else(|| default_name.into()),
+            entry_level: layer
+                .entry_level
+                .unwrap_or_else(|| TbPath::new_project_assets("levels/entry.tbasset")),
+            fps: layer.fps.unwrap_or(30f32),
+            plugins: layer.plugins.unwrap_or_default(),
+            asset_dirs: layer
+                .asset_dirs
+                .filter(|dirs| !dirs.is_empty())
+                .unwrap_or_else(|| {
+                    vec![PathBuf::from(crate::app_info::AppInfo::assets_dir_name())]
+                }),
+            frames: layer.frames,
+            time_limit: layer.time_limit,
+            headless: layer.headless.unwrap_or(false),
+            log_level: layer.log_level.unwrap_or_default(),
+        }
+    }
+
+    /// Reject the values the main loop can't run with
+    pub fn validate(&self) -> Result<()> {
+        if !self.fps.is_finite() || self.fps <= 0f32 {
+            bail!(ErrorKind::InvalidValue(
+                "fps".to_string(),
+                self.fps.to_string()
+            ));
+        }
+        if let Some(time_limit) = self.time_limit {
+            if !time_limit.is_finite() || time_limit < 0f32 {
+                bail!(ErrorKind::InvalidValue(
+                    "time limit".to_string(),
+                    time_limit.to_string()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One source of settings, the unset fields are taken from the lower sources
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SettingsLayer {
+    /// Only from the environment variables and the command line
+    #[serde(skip)]
+    pub project: Option<PathBuf>,
+    pub name: Option<String>,
+    pub entry_level: Option<TbPath>,
+    pub fps: Option<f32>,
+    pub plugins: Option<Vec<String>>,
+    pub asset_dirs: Option<Vec<PathBuf>>,
+    pub frames: Option<u64>,
+    pub time_limit: Option<f32>,
+    pub headless: Option<bool>,
+    pub log_level: Option<LogLevel>,
+}
+
+impl SettingsLayer {
+    pub fn from_toml(text: &str) -> Result<Self> {
+        toml::from_str(text).chain_err(|| "Failed to parse settings")
+    }
+
+    /// `None` if the file doesn't exist
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(path)
+            .chain_err(|| format!("Failed to read settings file. path: {:?}", path))?;
+        Self::from_toml(&text)
+            .chain_err(|| format!("Invalid settings file. path: {:?}", path))
+            .map(Some)
+    }
+
+    /// Read the variables named `TOYBOX_<FIELD>` by `var`, lists are separated by `,`
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let get = |name: &str| var(&format!("{}{}", Settings::ENV_PREFIX, name));
+        let parse_list = |value: String| -> Vec<String> {
+            value
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect()
+        };
+        Ok(SettingsLayer {
+            project: get("PROJECT").map(PathBuf::from),
+            name: get("NAME"),
+            entry_level: get("ENTRY_LEVEL")
+                .map(|value| parse_value("TOYBOX_ENTRY_LEVEL", &value))
+                .transpose()?,
+            fps: get("FPS")
+                .map(|value| parse_value("TOYBOX_FPS", &value))
+                .transpose()?,
+            plugins: get("PLUGINS").map(parse_list),
+            asset_dirs: get("ASSET_DIRS")
+                .map(|value| parse_list(value).into_iter().map(PathBuf::from).collect()),
+            frames: get("FRAMES")
+                .map(|value| parse_value("TOYBOX_FRAMES", &value))
+                .transpose()?,
+            time_limit: get("TIME_LIMIT")
+                .map(|value| parse_value("TOYBOX_TIME_LIMIT", &value))
+                .transpose()?,
+            headless: get("HEADLESS")
+                .map(|value| parse_bool("TOYBOX_HEADLESS", &value))
+                .transpose()?,
+            log_level: get("LOG_LEVEL")
+                .map(|value| parse_value("TOYBOX_LOG_LEVEL", &value))
+                .transpose()?,
+        })
+    }
+
+    /// Parse the command line arguments, without the program name
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut layer = SettingsLayer::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            layer.parse_arg(arg, &mut args)?;
+        }
+        Ok(layer)
+    }
+
+    /// Parse the command line arguments like `from_args`,
+    /// but skip the arguments which fail to parse and return their errors,
+    /// for the processes started with arguments of their own, such as the test harness
+    pub fn from_args_lenient(args: impl IntoIterator<Item = String>) -> (Self, Vec<Error>) {
+        let mut layer = SettingsLayer::default();
+        let mut errors = vec![];
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if let Err(e) = layer.parse_arg(arg, &mut args) {
+                errors.push(e);
+            }
+        }
+        (layer, errors)
+    }
+
+    fn parse_arg(&mut self, arg: String, args: &mut impl Iterator<Item = String>) -> Result<()> {
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => {
+                (name.to_string(), Some(value.to_string()))
+            }
+            _ => (arg.clone(), None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| Error::from(ErrorKind::MissingValue(name.clone())))
+        };
+        match name.as_str() {
+            "--help" | "-h" => bail!(ErrorKind::HelpRequested),
+            "--project" | "-p" => self.project = Some(PathBuf::from(value()?)),
+            "--name" => self.name = Some(value()?),
+            "--entry-level" => self.entry_level = Some(parse_value(&name, &value()?)?),
+            "--fps" => self.fps = Some(parse_value(&name, &value()?)?),
+            "--plugin" => self.plugins.get_or_insert_with(Vec::new).push(value()?),
+            "--asset-dir" => self
+                .asset_dirs
+                .get_or_insert_with(Vec::new)
+                .push(PathBuf::from(value()?)),
+            "--frames" => self.frames = Some(parse_value(&name, &value()?)?),
+            "--time-limit" => self.time_limit = Some(parse_value(&name, &value()?)?),
+            "--headless" => {
+                self.headless = Some(match inline_value {
+                    None => true,
+                    Some(value) => parse_bool(&name, &value)?,
+                })
+            }
+            "--log-level" => self.log_level = Some(parse_value(&name, &value()?)?),
+            _ => bail!(ErrorKind::UnknownArgument(arg)),
+        }
+        Ok(())
+    }
+
+    /// Fields set in `upper` override the ones of `self`
+    pub fn merge(self, upper: SettingsLayer) -> SettingsLayer {
+        SettingsLayer {
+            project: upper.project.or(self.project),
+            name: upper.name.or(self.name),
+            entry_level: upper.entry_level.or(self.entry_level),
+            fps: upper.fps.or(self.fps),
+            plugins: upper.plugins.or(self.plugins),
+            asset_dirs: upper.asset_dirs.or(self.asset_dirs),
+            frames: upper.frames.or(self.frames),
+            time_limit: upper.time_limit.or(self.time_limit),
+            headless: upper.headless.or(self.headless),
+            log_level: upper.log_level.or(self.log_level),
+        }
+    }
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| ErrorKind::InvalidValue(name.to_string(), value.to_string()).into())
+}
+
+fn parse_bool(name: &str, value: &str) -> Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => bail!(ErrorKind::InvalidValue(name.to_string(), value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use crate::path::TbPath;
+    use crate::settings::{LogLevel, Settings, SettingsErrorKind, SettingsLayer};
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn merge_layers() {
+        let file = SettingsLayer::from_toml(
+            r#"
+            name = "pong"
+            entry_level = "project://levels/menu.tbasset"
+            fps = 60.0
+            plugins = ["pong"]
+            asset_dirs = ["assets", "generated"]
+            log_level = "warn"
+            "#,
+        )
+        .unwrap();
+        let env: HashMap<_, _> = vec![
+            ("TOYBOX_FPS", "120"),
+            ("TOYBOX_LOG_LEVEL", "debug"),
+            ("TOYBOX_HEADLESS", "1"),
+            ("TOYBOX_TIME_LIMIT", "2.5"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let env = SettingsLayer::from_env(|key| env.get(key).cloned()).unwrap();
+        let cli = SettingsLayer::from_args(args(
+            "-p ../pong --frames 10 --log-level=error --entry-level project://levels/a/../b.tbasset",
+        ))
+        .unwrap();
+        assert_eq!(cli.project, Some(PathBuf::from("../pong")));
+
+        let settings = Settings::new(file.merge(env).merge(cli), "default");
+        assert_eq!(
+            settings,
+            Settings {
+                name: "pong".to_string(),
+                entry_level: TbPath::new_project_assets("levels/b.tbasset"),
+                fps: 120.0,
+                plugins: vec!["pong".to_string()],
+                asset_dirs: vec![PathBuf::from("assets"), PathBuf::from("generated")],
+                frames: Some(10),
+                time_limit: Some(2.5),
+                headless: true,
+                log_level: LogLevel::Error,
+            }
+        );
+
+        let settings = Settings::new(SettingsLayer::default(), "default");
+        assert_eq!(settings.name, "default");
+        assert_eq!(settings.asset_dirs, vec![PathBuf::from("assets")]);
+        assert!(!settings.headless);
+    }
+
+    #[test]
+    fn invalid_args() {
+        let kind = |a: &str| {
+            SettingsLayer::from_args(args(a))
+                .unwrap_err()
+                .kind()
+                .to_string()
+        };
+        assert_eq!(kind("--help"), SettingsErrorKind::HelpRequested.to_string());
+        assert!(kind("--unknown").starts_with("Unknown argument"));
+        assert!(kind("--frames").starts_with("Missing value"));
+        assert!(kind("--frames ten").starts_with("Invalid value"));
+        assert!(kind("--log-level loud").starts_with("Invalid value"));
+        assert!(SettingsLayer::from_toml("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn lenient_args() {
+        let (layer, errors) = SettingsLayer::from_args_lenient(args(
+            "filter --nocapture --frames 3 --fps fast --test-threads=1 --headless",
+        ));
+        assert_eq!(errors.len(), 4);
+        assert_eq!(layer.frames, Some(3));
+        assert_eq!(layer.fps, None);
+        assert_eq!(layer.headless, Some(true));
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let settings =
+            |a: &str| Settings::new(SettingsLayer::from_args(args(a)).unwrap(), "test").validate();
+        assert!(settings("--fps 60 --time-limit 0").is_ok());
+        assert!(settings("--fps 0").is_err());
+        assert!(settings("--fps -30").is_err());
+        assert!(settings("--fps NaN").is_err());
+        assert!(settings("--fps inf").is_err());
+        assert!(settings("--time-limit -1").is_err());
+        assert!(settings("--time-limit inf").is_err());
+        assert!(settings("--time-limit NaN").is_err());
+
+        let file = SettingsLayer::from_toml("fps = 0.0").unwrap();
+        assert!(Settings::new(file, "test").validate().is_err());
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+use tb_core::hash::fnv1a_64;
+
+use crate::vfs::errors::*;
+use crate::vfs::{normalize, FileSystem};
+
+trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// The stored data of the entry is compressed by deflate
+const FLAG_COMPRESSED: u32 = 1;
+
+/// Size of an index record without the path
+const RECORD_SIZE: u64 = 4 + 8 + 8 + 8 + 4 + 8;
+
+const MAX_PATH_LEN: u64 = 4096;
+
+/// Largest original size of an entry, rejects corrupt sizes before allocating
+const MAX_FILE_SIZE: u64 = 1 << 32;
+
+struct ArchiveEntry {
+    offset: u64,
+    stored_size: u64,
+    size: u64,
+    flags: u32,
+    hash: u64,
+}
+
+impl ArchiveEntry {
+    /// Check the sizes against the archive before anything is allocated from them
+    fn validate(&self, archive_len: u64) -> Result<()> {
+        match self.offset.checked_add(self.stored_size) {
+            Some(end) if end <= archive_len => {}
+            _ => bail!(invalid(format!(
+                "data at {} of size {} exceeds the archive of size {}",
+                self.offset, self.stored_size, archive_len
+            ))),
+        }
+        if self.size > MAX_FILE_SIZE {
+            bail!(invalid(format!("size {} is too large", self.size)));
+        }
+        if self.flags & FLAG_COMPRESSED == 0 && self.size != self.stored_size {
+            bail!(invalid(format!(
+                "size {} differs from the stored size {} of uncompressed data",
+                self.size, self.stored_size
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Read only files packed in a single archive file.
+///
+/// Layout, integers in little endian:
+/// * header: magic `TBPK`, version `u32`, entry count `u32`
+/// * index, one record per entry:
+///   - path length `u32` and the path in utf8, relative to the packed dir and separated by `/`
+///   - offset `u64` of the stored data from the start of the archive
+///   - stored size `u64` and original size `u64`
+///   - flags `u32`, bit 0 set if the stored data is compressed by deflate
+///   - FNV-1a 64 hash `u64` of the original data, checked on read
+/// * stored data of the entries
+pub struct ArchiveFileSystem {
+    source: Mutex<Box<dyn ReadSeek>>,
+    entries: BTreeMap<PathBuf, ArchiveEntry>,
+}
+
+impl ArchiveFileSystem {
+    pub const MAGIC: &'static [u8; 4] = b"TBPK";
+    pub const VERSION: u32 = 2;
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).chain_err(|| format!("Failed to open archive. path: {:?}", path))?;
+        Self::from_source(Box::new(file))
+            .chain_err(|| format!("Failed to read archive. path: {:?}", path))
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        Self::from_source(Box::new(Cursor::new(bytes)))
+    }
+
+    fn from_source(mut source: Box<dyn ReadSeek>) -> Result<Self> {
+        let archive_len = source.seek(SeekFrom::End(0))?;
+        source.seek(SeekFrom::Start(0))?;
+        let mut magic = [0u8; 4];
+        source.read_exact(&mut magic)?;
+        if &magic != Self::MAGIC {
+            bail!(ErrorKind::InvalidArchive("wrong magic".to_string()));
+        }
+        let version = read_u32(&mut source)?;
+        if version != Self::VERSION {
+            bail!(ErrorKind::InvalidArchive(format!(
+                "unsupported version {}",
+                version
+            )));
+        }
+        let count = read_u32(&mut source)?;
+        if count as u64 * RECORD_SIZE > archive_len {
+            bail!(invalid(format!(
+                "entry count {} exceeds the archive",
+                count
+            )));
+        }
+        let mut entries = BTreeMap::new();
+        for _ in 0..count {
+            let path_len = read_u32(&mut source)? as u64;
+            let remaining = archive_len.saturating_sub(source.stream_position()?);
+            if path_len > MAX_PATH_LEN || path_len > remaining {
+                bail!(invalid(format!("path length {} is too long", path_len)));
+            }
+            let mut path = vec![0u8; path_len as usize];
+            source.read_exact(&mut path)?;
+            let path = String::from_utf8(path)
+                .map_err(|_| ErrorKind::InvalidArchive("path is not utf8".to_string()))?;
+            let entry = ArchiveEntry {
+                offset: read_u64(&mut source)?,
+                stored_size: read_u64(&mut source)?,
+                size: read_u64(&mut source)?,
+                flags: read_u32(&mut source)?,
+                hash: read_u64(&mut source)?,
+            };
+            entry.validate(archive_len).chain_err(|| {
+                ErrorKind::InvalidArchive(format!("corrupted index of {:?}", path))
+            })?;
+            entries.insert(PathBuf::from(path), entry);
+        }
+        let index_end = source.stream_position()?;
+        if let Some((path, _)) = entries.iter().find(|(_, entry)| entry.offset < index_end) {
+            bail!(invalid(format!("data of {:?} overlaps the index", path)));
+        }
+        Ok(Self {
+            source: Mutex::new(source),
+            entries,
+        })
+    }
+
+    /// Paths of all the files in the archive
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.entries.keys().map(|path| path.as_path())
+    }
+}
+
+impl FileSystem for ArchiveFileSystem {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let entry = self
+            .entries
+            .get(Path::new(&archive_path(path)?))
+            .ok_or_else(|| Error::from(ErrorKind::NotFound(path.to_owned())))?;
+        // the sizes are read from the archive, the buffers grow with the data actually read
+        let mut stored = vec![];
+        {
+            let mut source = self.source.lock().unwrap();
+            source.seek(SeekFrom::Start(entry.offset))?;
+            (&mut *source)
+                .take(entry.stored_size)
+                .read_to_end(&mut stored)?;
+        }
+        if stored.len() as u64 != entry.stored_size {
+            bail!(invalid(format!("truncated entry {:?}", path)));
+        }
+        let data = if entry.flags & FLAG_COMPRESSED != 0 {
+            let mut data = vec![];
+            // one more byte than expected is enough to detect a wrong size
+            DeflateDecoder::new(stored.as_slice())
+                .take(entry.size + 1)
+                .read_to_end(&mut data)?;
+            data
+        } else {
+            stored
+        };
+        if data.len() as u64 != entry.size || fnv1a_64(&data) != entry.hash {
+            bail!(ErrorKind::InvalidArchive(format!(
+                "corrupted entry {:?}",
+                path
+            )));
+        }
+        Ok(data)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        let path = match archive_path(path) {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => return false,
+        };
+        self.entries
+            .range(path.clone()..)
+            .next()
+            .map_or(false, |(entry, _)| entry.starts_with(&path))
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let path = PathBuf::from(archive_path(path)?);
+        let mut entries: Vec<PathBuf> = self
+            .entries
+            .range(path.clone()..)
+            .map(|(entry, _)| entry)
+            .take_while(|entry| entry.starts_with(&path))
+            .filter_map(|entry| entry.strip_prefix(&path).unwrap().components().next())
+            .map(|child| path.join(child))
+            .collect();
+        if entries.is_empty() {
+            bail!(ErrorKind::NotFound(path));
+        }
+        entries.dedup();
+        Ok(entries)
+    }
+}
+
+/// Builds an archive in the layout of `ArchiveFileSystem`.
+/// An entry is compressed only if it gets smaller
+#[derive(Default)]
+pub struct Packer {
+    files: BTreeMap<String, Vec<u8>>,
+    no_compression: bool,
+}
+
+impl Packer {
+    /// Pack the `assets` dir of a project into `dest`
+    pub fn pack_assets(project_dir: impl AsRef<Path>, dest: impl AsRef<Path>) -> Result<()> {
+        let assets_dir = project_dir
+            .as_ref()
+            .join(crate::app_info::AppInfo::assets_dir_name());
+        let mut packer = Packer::default();
+        packer.add_dir(&assets_dir, "")?;
+        packer.write_to_file(dest)
+    }
+
+    pub fn set_compression(&mut self, compression: bool) {
+        self.no_compression = !compression;
+    }
+
+    pub fn add_file(&mut self, path: impl AsRef<Path>, data: Vec<u8>) -> Result<()> {
+        self.files.insert(archive_path(path.as_ref())?, data);
+        Ok(())
+    }
+
+    /// Add the files in `dir` recursively, as the paths under `prefix`
+    pub fn add_dir(&mut self, dir: impl AsRef<Path>, prefix: impl AsRef<Path>) -> Result<()> {
+        let dir = dir.as_ref();
+        let prefix = prefix.as_ref();
+        let entries =
+            std::fs::read_dir(dir).chain_err(|| format!("Failed to read dir. path: {:?}", dir))?;
+        for entry in entries {
+            let entry = entry?;
+            let path = prefix.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                self.add_dir(entry.path(), path)?;
+            } else {
+                let data = std::fs::read(entry.path())
+                    .chain_err(|| format!("Failed to read file. path: {:?}", entry.path()))?;
+                self.add_file(path, data)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_to_file(&self, dest: impl AsRef<Path>) -> Result<()> {
+        let dest = dest.as_ref();
+        let file = File::create(dest)
+            .chain_err(|| format!("Failed to create archive. path: {:?}", dest))?;
+        let mut writer = BufWriter::new(file);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
+        let mut stored = Vec::with_capacity(self.files.len());
+        for data in self.files.values() {
+            stored.push(self.compress(data)?);
+        }
+        let index_size: usize = self
+            .files
+            .keys()
+            .map(|path| 4 + path.len() + 8 + 8 + 8 + 4 + 8)
+            .sum();
+        let mut offset = (4 + 4 + 4 + index_size) as u64;
+
+        writer.write_all(ArchiveFileSystem::MAGIC)?;
+        writer.write_all(&ArchiveFileSystem::VERSION.to_le_bytes())?;
+        writer.write_all(&(self.files.len() as u32).to_le_bytes())?;
+        for ((path, data), (stored, flags)) in self.files.iter().zip(&stored) {
+            writer.write_all(&(path.len() as u32).to_le_bytes())?;
+            writer.write_all(path.as_bytes())?;
+            writer.write_all(&offset.to_le_bytes())?;
+            writer.write_all(&(stored.len() as u64).to_le_bytes())?;
+            writer.write_all(&(data.len() as u64).to_le_bytes())?;
+            writer.write_all(&flags.to_le_bytes())?;
+            writer.write_all(&fnv1a_64(data).to_le_bytes())?;
+            offset += stored.len() as u64;
+        }
+        for (stored, _) in &stored {
+            writer.write_all(stored)?;
+        }
+        Ok(())
+    }
+
+    fn compress(&self, data: &[u8]) -> Result<(Vec<u8>, u32)> {
+        if !self.no_compression {
+            let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+            encoder.write_all(data)?;
+            let compressed = encoder.finish()?;
+            if compressed.len() < data.len() {
+                return Ok((compressed, FLAG_COMPRESSED));
+            }
+        }
+        Ok((data.to_vec(), 0))
+    }
+}
+
+/// Normalized path separated by `/`
+fn archive_path(path: &Path) -> Result<String> {
+    Ok(normalize(path)?
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+fn invalid(reason: String) -> ErrorKind {
+    ErrorKind::InvalidArchive(reason)
+}
+
+fn read_u32(source: &mut impl Read) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    source.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(source: &mut impl Read) -> Result<u64> {
+    let mut bytes = [0u8; 8];
+    source.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
@@ -0,0 +1,97 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use crate::vfs::errors::*;
+use crate::vfs::{normalize, FileSystem};
+
+/// Files in a directory on disk
+pub struct DirFileSystem {
+    root: PathBuf,
+}
+
+impl DirFileSystem {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn full_path(&self, path: &Path) -> Result<PathBuf> {
+        Ok(self.root.join(normalize(path)?))
+    }
+}
+
+impl FileSystem for DirFileSystem {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let path = self.full_path(path)?;
+        std::fs::read(&path).chain_err(|| format!("Failed to read file. path: {:?}", path))
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + Send>> {
+        let path = self.full_path(path)?;
+        let file =
+            File::open(&path).chain_err(|| format!("Failed to open file. path: {:?}", path))?;
+        Ok(Box::new(file))
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        write_atomic(&self.full_path(path)?, data)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.full_path(path).map_or(false, |path| path.exists())
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let dir = self.full_path(path)?;
+        std::fs::read_dir(&dir)
+            .chain_err(|| format!("Failed to read dir. path: {:?}", dir))?
+            .map(|entry| Ok(path.join(entry?.file_name())))
+            .collect()
+    }
+
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(self.full_path(path).ok()?)
+            .and_then(|meta| meta.modified())
+            .ok()
+    }
+
+    fn real_path(&self, path: &Path) -> Option<PathBuf> {
+        self.full_path(path).ok()
+    }
+}
+
+/// Write to a temp file next to `path` then rename it to `path`,
+/// so readers never see a partially written file. The parent dirs are created.
+/// Every write has its own temp file, so concurrent writes to the same path don't interleave
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .chain_err(|| format!("Failed to create dir. path: {:?}", parent))?;
+    }
+    let mut temp_name = OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp = path.with_file_name(temp_name);
+    std::fs::write(&temp, data).chain_err(|| format!("Failed to write file. path: {:?}", temp))?;
+    std::fs::rename(&temp, path).or_else(|e| {
+        let _ = std::fs::remove_file(&temp);
+        Err(e).chain_err(|| format!("Failed to rename file. from: {:?}, to: {:?}", temp, path))
+    })
+}
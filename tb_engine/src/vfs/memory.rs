@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+
+use crate::vfs::errors::*;
+use crate::vfs::{normalize, FileSystem};
+
+/// Files kept in memory, mainly for tests
+#[derive(Default)]
+pub struct MemoryFileSystem {
+    files: RwLock<BTreeMap<PathBuf, (Vec<u8>, SystemTime)>>,
+}
+
+impl MemoryFileSystem {
+    /// Insert or replace a file, the modification time of a replaced file always increases.
+    /// Panics if `path` is rooted or escapes the root
+    pub fn insert(&self, path: impl AsRef<Path>, data: Vec<u8>) {
+        let path = normalize(path.as_ref()).unwrap_or_else(|e| panic!("{}", e));
+        let mut files = self.files.write().unwrap();
+        let now = SystemTime::now();
+        let modified = match files.get(&path) {
+            Some((_, previous)) if *previous >= now => *previous + Duration::from_nanos(1),
+            _ => now,
+        };
+        files.insert(path, (data, modified));
+    }
+
+    pub fn remove(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        self.files
+            .write()
+            .unwrap()
+            .remove(&normalize(path.as_ref()).ok()?)
+            .map(|(data, _)| data)
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.files
+            .read()
+            .unwrap()
+            .get(&normalize(path)?)
+            .map(|(data, _)| data.clone())
+            .ok_or_else(|| ErrorKind::NotFound(path.to_owned()).into())
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.insert(normalize(path)?, data.to_vec());
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        normalize(path).map_or(false, |path| {
+            self.files
+                .read()
+                .unwrap()
+                .keys()
+                .any(|file| file.starts_with(&path))
+        })
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let path = normalize(path)?;
+        let files = self.files.read().unwrap();
+        let mut entries: Vec<PathBuf> = files
+            .keys()
+            .filter_map(|file| file.strip_prefix(&path).ok())
+            .filter_map(|relative| relative.components().next())
+            .map(|child| path.join(child))
+            .collect();
+        if entries.is_empty() {
+            bail!(ErrorKind::NotFound(path));
+        }
+        entries.dedup();
+        Ok(entries)
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        self.files
+            .read()
+            .unwrap()
+            .get(&normalize(path).ok()?)
+            .map(|(_, modified)| *modified)
+    }
+
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
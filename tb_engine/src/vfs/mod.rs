@@ -0,0 +1,500 @@
+use std::cmp::Reverse;
+use std::io::{Cursor, Read};
+use std::lazy::SyncLazy;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+pub use archive::{ArchiveFileSystem, Packer};
+pub use dir::DirFileSystem;
+pub use errors::{Error as VfsError, ErrorKind as VfsErrorKind, Result as VfsResult};
+pub use memory::MemoryFileSystem;
+
+use crate::app_info::AppInfo;
+
+mod archive;
+mod dir;
+mod memory;
+
+mod errors {
+    pub use tb_core::error::*;
+
+    error_chain! {
+        foreign_links {
+            Io(std::io::Error);
+        }
+
+        errors {
+            NotFound(path: std::path::PathBuf) {
+                description("File not found"),
+                display("File not found. path: {:?}", path),
+            }
+            ReadOnly(path: std::path::PathBuf) {
+                description("File system is read only"),
+                display("File system is read only. path: {:?}", path),
+            }
+            EscapeRoot(path: std::path::PathBuf) {
+                description("Path is rooted or escapes the root of the file system"),
+                display("Path is rooted or escapes the root of the file system. path: {:?}", path),
+            }
+            InvalidArchive(reason: String) {
+                description("Invalid archive"),
+                display("Invalid archive. reason: {}", reason),
+            }
+        }
+    }
+}
+
+use errors::*;
+
+/// A backend mounted into the `Vfs`. Paths are relative to the root of the backend
+pub trait FileSystem: Send + Sync {
+    fn read(&self, path: &Path) -> Result<Vec<u8>>;
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + Send>> {
+        Ok(Box::new(Cursor::new(self.read(path)?)))
+    }
+
+    fn write(&self, path: &Path, _data: &[u8]) -> Result<()> {
+        Err(ErrorKind::ReadOnly(path.to_owned()).into())
+    }
+
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Paths of the files and dirs directly in `path`, relative to the root of the backend
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>>;
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    /// Last modification time, if the backend tracks it
+    fn modified(&self, _path: &Path) -> Option<SystemTime> {
+        None
+    }
+
+    /// The path on disk, if the backend is backed by a directory
+    fn real_path(&self, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+}
+
+struct Mount {
+    point: PathBuf,
+    fs: Arc<dyn FileSystem>,
+}
+
+/// Mount table of file systems. Relative paths resolve through the mount with the longest matching point,
+/// the later mounted one wins at the same point. Absolute paths go to the disk directly
+#[derive(Default)]
+pub struct Vfs {
+    mounts: RwLock<Vec<Mount>>,
+}
+
+impl Vfs {
+    pub const ENGINE_MOUNT_POINT: &'static str = "engine";
+    pub const PROJECT_MOUNT_POINT: &'static str = "project";
+
+    /// The vfs used by `TbPath`, mounting the engine and project dirs of `AppInfo`,
+    /// the asset dirs of the settings and the project archive over the project assets
+    pub fn get() -> &'static Vfs {
+        static INSTANCE: SyncLazy<Vfs> = SyncLazy::new(|| {
+            let app_info = AppInfo::get();
+            let vfs = Vfs::default();
+            vfs.mount(
+                Vfs::ENGINE_MOUNT_POINT,
+                Arc::new(DirFileSystem::new(&app_info.engine_root_dir)),
+            );
+            vfs.mount(
+                Vfs::PROJECT_MOUNT_POINT,
+                Arc::new(DirFileSystem::new(&app_info.project_root_dir)),
+            );
+            let project_assets =
+                Path::new(Vfs::PROJECT_MOUNT_POINT).join(AppInfo::assets_dir_name());
+            for asset_dir in app_info.settings.asset_dirs.iter().rev() {
+                vfs.mount(
+                    &project_assets,
+                    Arc::new(DirFileSystem::new(
+                        app_info.project_root_dir.join(asset_dir),
+                    )),
+                );
+            }
+            if let Some(archive) = &app_info.project_archive {
+                match ArchiveFileSystem::open(archive) {
+                    Ok(archive) => vfs.mount(&project_assets, Arc::new(archive)),
+                    Err(e) => log_error!("{}", e.display_chain()),
+                }
+            }
+            vfs
+        });
+
+        &INSTANCE
+    }
+
+    /// Panics if `point` is rooted or escapes the root
+    pub fn mount(&self, point: impl AsRef<Path>, fs: Arc<dyn FileSystem>) {
+        let point = normalize(point.as_ref()).unwrap_or_else(|e| panic!("{}", e));
+        self.mounts.write().unwrap().push(Mount { point, fs });
+    }
+
+    /// Unmount the file system mounted last at `point`
+    pub fn unmount(&self, point: impl AsRef<Path>) -> Option<Arc<dyn FileSystem>> {
+        let point = normalize(point.as_ref()).ok()?;
+        let mut mounts = self.mounts.write().unwrap();
+        let index = mounts.iter().rposition(|mount| mount.point == point)?;
+        Some(mounts.remove(index).fs)
+    }
+
+    pub fn read(&self, path: impl AsRef<Path>) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+        if path.has_root() {
+            return Ok(std::fs::read(path)?);
+        }
+        let (fs, relative) = self.resolve_existing(path)?;
+        fs.read(&relative)
+    }
+
+    pub fn open(&self, path: impl AsRef<Path>) -> Result<Box<dyn Read + Send>> {
+        let path = path.as_ref();
+        if path.has_root() {
+            return Ok(Box::new(std::fs::File::open(path)?));
+        }
+        let (fs, relative) = self.resolve_existing(path)?;
+        fs.open(&relative)
+    }
+
+    /// Write to the last mounted writable file system containing `path`.
+    /// Files on disk are replaced atomically and their parent dirs are created
+    pub fn write(&self, path: impl AsRef<Path>, data: &[u8]) -> Result<()> {
+        let path = path.as_ref();
+        if path.has_root() {
+            return dir::write_atomic(path, data);
+        }
+        let (fs, relative) = self
+            .resolve(path)?
+            .into_iter()
+            .find(|(fs, _)| !fs.is_read_only())
+            .ok_or_else(|| Error::from(ErrorKind::ReadOnly(path.to_owned())))?;
+        fs.write(&relative, data)
+    }
+
+    /// Whether `path` is in a read-only file system, the one holding it if it exists.
+    /// Paths on disk are not read-only
+    pub fn is_read_only(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        if path.has_root() {
+            return false;
+        }
+        match self.resolve_existing(path) {
+            Ok((fs, _)) => fs.is_read_only(),
+            Err(_) => self.resolve(path).map_or(true, |resolved| {
+                resolved.iter().all(|(fs, _)| fs.is_read_only())
+            }),
+        }
+    }
+
+    pub fn exists(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        if path.has_root() {
+            return path.exists();
+        }
+        self.resolve_existing(path).is_ok()
+    }
+
+    /// Last modification time of `path`, `None` if missing or not tracked by its file system
+    pub fn modified(&self, path: impl AsRef<Path>) -> Option<SystemTime> {
+        let path = path.as_ref();
+        if path.has_root() {
+            return std::fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .ok();
+        }
+        let (fs, relative) = self.resolve_existing(path).ok()?;
+        fs.modified(&relative)
+    }
+
+    /// Entries of all the file systems mounted at `path`, as virtual paths
+    pub fn read_dir(&self, path: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let path = path.as_ref();
+        if path.has_root() {
+            return std::fs::read_dir(path)?
+                .map(|entry| Ok(entry?.path()))
+                .collect();
+        }
+        let path = normalize(path)?;
+        let mut entries = vec![];
+        let mut found = false;
+        for (fs, relative) in self.resolve(&path)? {
+            if let Ok(fs_entries) = fs.read_dir(&relative) {
+                found = true;
+                let prefix = path_prefix(&path, &relative);
+                entries.extend(fs_entries.into_iter().map(|entry| prefix.join(entry)));
+            }
+        }
+        if !found {
+            bail!(ErrorKind::NotFound(path));
+        }
+        entries.sort();
+        entries.dedup();
+        Ok(entries)
+    }
+
+    /// The path on disk of `path`, if it resolves to a directory backend
+    pub fn real_path(&self, path: impl AsRef<Path>) -> Option<PathBuf> {
+        let path = path.as_ref();
+        if path.has_root() {
+            return Some(path.to_owned());
+        }
+        let resolved = self.resolve(path).ok()?;
+        resolved
+            .iter()
+            .find(|(fs, relative)| fs.exists(relative))
+            .or_else(|| resolved.first())
+            .and_then(|(fs, relative)| fs.real_path(relative))
+    }
+
+    fn resolve_existing(&self, path: &Path) -> Result<(Arc<dyn FileSystem>, PathBuf)> {
+        self.resolve(path)?
+            .into_iter()
+            .find(|(fs, relative)| fs.exists(relative))
+            .ok_or_else(|| ErrorKind::NotFound(path.to_owned()).into())
+    }
+
+    /// File systems containing `path` with the path relative to them, in the order of priority
+    fn resolve(&self, path: &Path) -> Result<Vec<(Arc<dyn FileSystem>, PathBuf)>> {
+        let path = normalize(path)?;
+        let mounts = self.mounts.read().unwrap();
+        let mut resolved: Vec<_> = mounts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, mount)| {
+                path.strip_prefix(&mount.point)
+                    .ok()
+                    .map(|relative| (mount.point.components().count(), i, relative.to_owned()))
+            })
+            .collect();
+        resolved.sort_by_key(|(depth, i, _)| Reverse((*depth, *i)));
+        Ok(resolved
+            .into_iter()
+            .map(|(_, i, relative)| (mounts[i].fs.clone(), relative))
+            .collect())
+    }
+}
+
+/// The part of the normalized `path` before `relative`
+fn path_prefix(path: &Path, relative: &Path) -> PathBuf {
+    let count = path.components().count() - relative.components().count();
+    path.components().take(count).collect()
+}
+
+/// Drop `.` and resolve `..` lexically, rooted paths and `..` out of the root are errors
+fn normalize(path: &Path) -> Result<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    bail!(ErrorKind::EscapeRoot(path.to_owned()));
+                }
+            }
+            Component::Normal(name) => normalized.push(name),
+            Component::RootDir | Component::Prefix(_) => {
+                bail!(ErrorKind::EscapeRoot(path.to_owned()))
+            }
+        }
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    use crate::vfs::{
+        ArchiveFileSystem, DirFileSystem, FileSystem, MemoryFileSystem, Packer, Vfs, VfsError,
+        VfsErrorKind,
+    };
+
+    #[test]
+    fn mount_overlay() {
+        let vfs = Vfs::default();
+        let base = MemoryFileSystem::default();
+        base.insert("assets/a.txt", b"base a".to_vec());
+        base.insert("assets/b.txt", b"base b".to_vec());
+        vfs.mount("project", Arc::new(base));
+        let patch = MemoryFileSystem::default();
+        patch.insert("a.txt", b"patch a".to_vec());
+        vfs.mount("project/assets", Arc::new(patch));
+
+        assert_eq!(vfs.read("project/assets/a.txt").unwrap(), b"patch a");
+        assert_eq!(
+            vfs.read("project/./assets/../assets/b.txt").unwrap(),
+            b"base b"
+        );
+        assert!(vfs.read("project/assets/c.txt").is_err());
+        match vfs.read("project/../../a.txt") {
+            Err(VfsError(VfsErrorKind::EscapeRoot(_), _)) => {}
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+        assert!(vfs.write("../a.txt", b"a").is_err());
+        assert!(!vfs.exists("project/../.."));
+        let dir = DirFileSystem::new(std::env::temp_dir().join("tb_vfs_escape"));
+        assert!(dir.read(Path::new("../a.txt")).is_err());
+        assert!(dir.real_path(Path::new("/a.txt")).is_none());
+        assert_eq!(
+            vfs.read_dir("project/assets").unwrap(),
+            vec![
+                PathBuf::from("project/assets/a.txt"),
+                PathBuf::from("project/assets/b.txt")
+            ]
+        );
+
+        vfs.write("project/assets/c.txt", b"c").unwrap();
+        assert_eq!(vfs.read("project/assets/c.txt").unwrap(), b"c");
+
+        assert!(vfs.unmount("project/assets").is_some());
+        assert_eq!(vfs.read("project/assets/a.txt").unwrap(), b"base a");
+        assert!(!vfs.exists("project/assets/c.txt"));
+    }
+
+    #[test]
+    fn modified() {
+        let vfs = Vfs::default();
+        let fs = Arc::new(MemoryFileSystem::default());
+        fs.insert("a.txt", b"a".to_vec());
+        vfs.mount("project", fs.clone());
+        let first = vfs.modified("project/a.txt").unwrap();
+        assert_eq!(vfs.modified("project/a.txt"), Some(first));
+        assert_eq!(vfs.modified("project/b.txt"), None);
+
+        fs.insert("a.txt", b"aa".to_vec());
+        let second = vfs.modified("project/a.txt").unwrap();
+        assert!(second > first);
+        fs.insert("a.txt", b"aaa".to_vec());
+        assert!(vfs.modified("project/a.txt").unwrap() > second);
+    }
+
+    #[test]
+    fn atomic_write() {
+        let root = std::env::temp_dir().join(format!("tb_vfs_atomic_write_{}", std::process::id()));
+        let vfs = Vfs::default();
+        vfs.mount("project", Arc::new(DirFileSystem::new(&root)));
+        vfs.write("project/levels/entry.tbasset", b"old").unwrap();
+        vfs.write("project/levels/entry.tbasset", b"new").unwrap();
+        assert_eq!(vfs.read("project/levels/entry.tbasset").unwrap(), b"new");
+        assert_eq!(
+            vfs.read_dir("project/levels").unwrap(),
+            vec![PathBuf::from("project/levels/entry.tbasset")]
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn concurrent_atomic_writes() {
+        let root = std::env::temp_dir().join(format!(
+            "tb_vfs_concurrent_atomic_writes_{}",
+            std::process::id()
+        ));
+        let fs = Arc::new(DirFileSystem::new(&root));
+        let contents: Vec<Vec<u8>> = (0..8u8).map(|i| vec![i; 64 * 1024]).collect();
+        let threads: Vec<_> = contents
+            .iter()
+            .cloned()
+            .map(|data| {
+                let fs = fs.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..4 {
+                        fs.write(Path::new("a.bin"), &data).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert!(contents.contains(&fs.read(Path::new("a.bin")).unwrap()));
+        assert_eq!(
+            fs.read_dir(Path::new("")).unwrap(),
+            vec![PathBuf::from("a.bin")]
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn archive() {
+        let level = b"{\"entities\": []}".repeat(100);
+        let mut packer = Packer::default();
+        packer
+            .add_file("levels/entry.tbasset", level.clone())
+            .unwrap();
+        packer
+            .add_file("textures/./a.png", vec![0, 1, 2, 3])
+            .unwrap();
+        let mut bytes = vec![];
+        packer.write(&mut bytes).unwrap();
+        assert!(bytes.len() < level.len());
+        let archive = ArchiveFileSystem::from_bytes(bytes.clone()).unwrap();
+
+        let vfs = Vfs::default();
+        vfs.mount("engine/assets", Arc::new(archive));
+        assert_eq!(
+            vfs.read("engine/assets/textures/a.png").unwrap(),
+            vec![0, 1, 2, 3]
+        );
+        assert_eq!(
+            vfs.read_dir("engine/assets").unwrap(),
+            vec![
+                PathBuf::from("engine/assets/levels"),
+                PathBuf::from("engine/assets/textures")
+            ]
+        );
+        assert_eq!(
+            vfs.read("engine/assets/levels/entry.tbasset").unwrap(),
+            level
+        );
+        assert!(vfs
+            .write("engine/assets/levels/entry.tbasset", b"")
+            .is_err());
+        assert_eq!(vfs.modified("engine/assets/textures/a.png"), None);
+
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        let corrupted = ArchiveFileSystem::from_bytes(bytes).unwrap();
+        assert!(corrupted.read(Path::new("textures/a.png")).is_err());
+    }
+
+    #[test]
+    fn archive_corrupted_header() {
+        let mut packer = Packer::default();
+        packer.add_file("a.txt", b"a".repeat(100)).unwrap();
+        let mut bytes = vec![];
+        packer.write(&mut bytes).unwrap();
+        assert!(ArchiveFileSystem::from_bytes(bytes.clone()).is_ok());
+
+        let corrupt = |at: usize, value: &[u8]| {
+            let mut bytes = bytes.clone();
+            bytes[at..at + value.len()].copy_from_slice(value);
+            ArchiveFileSystem::from_bytes(bytes)
+        };
+        let count = 8;
+        let path_len = 12;
+        let offset = path_len + 4 + "a.txt".len();
+        let stored_size = offset + 8;
+        let size = stored_size + 8;
+        assert!(corrupt(count, &u32::MAX.to_le_bytes()).is_err());
+        assert!(corrupt(path_len, &u32::MAX.to_le_bytes()).is_err());
+        assert!(corrupt(offset, &u64::MAX.to_le_bytes()).is_err());
+        assert!(corrupt(offset, &0u64.to_le_bytes()).is_err());
+        assert!(corrupt(stored_size, &u64::MAX.to_le_bytes()).is_err());
+        assert!(corrupt(size, &u64::MAX.to_le_bytes()).is_err());
+        assert!(ArchiveFileSystem::from_bytes(bytes[..bytes.len() - 1].to_vec()).is_err());
+
+        let archive = corrupt(size, &1u64.to_le_bytes()).unwrap();
+        assert!(archive.read(Path::new("a.txt")).is_err());
+        // not allocated up front
+        let archive = corrupt(size, &(1u64 << 32).to_le_bytes()).unwrap();
+        assert!(archive.read(Path::new("a.txt")).is_err());
+    }
+}
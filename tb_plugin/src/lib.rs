@@ -48,7 +48,8 @@ impl LibPartner for Box<dyn Plugin> {
         println!("Loaded plugin: {}", plugin.name());
         plugin.on_load();
         SystemRegistry::add_system_infos(plugin.system_infos());
-        ComponentRegistry::add_component_infos(plugin.component_infos());
+        ComponentRegistry::add_component_infos(plugin.component_infos())
+            .chain_err(|| format!("Failed to register components of plugin: {}", plugin.name()))?;
         Ok(plugin)
     }
 